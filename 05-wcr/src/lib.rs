@@ -1,7 +1,10 @@
 use clap::Parser;
+use flate2::bufread::MultiGzDecoder;
+use std::cell::Cell;
 use std::error::Error;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::rc::Rc;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -30,6 +33,15 @@ pub struct Config {
 
     #[arg(help = "Count of number of characters", short = 'm', long)]
     chars: bool,
+
+    #[arg(help = "Treat all input as gzip-compressed", long)]
+    gzip: bool,
+
+    #[arg(help = "Report compressed (on-disk) bytes for gzip input", long)]
+    compressed_bytes: bool,
+
+    #[arg(help = "Flush each file's counts as soon as it is done", long)]
+    stream: bool,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -52,33 +64,57 @@ pub fn get_args() -> MyResult<Config> {
         bytes,
         chars: config.chars,
         files: config.files,
+        gzip: config.gzip,
+        compressed_bytes: config.compressed_bytes,
+        stream: config.stream,
     })
 }
 
 pub fn run(config: Config) -> MyResult<()> {
     let mut total: FileInfo = Default::default();
+    let mut out = BufWriter::new(io::stdout());
 
     for filename in &config.files {
-        match open(filename) {
+        match open(filename, config.gzip) {
             Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(file) => {
-                let file_info = count(file).unwrap();
+            Ok(input) => {
+                // A read can fail part way, as on corrupt gzip data
+                let mut file_info = match count(input.reader) {
+                    Ok(file_info) => file_info,
+                    Err(err) => {
+                        eprintln!("wcr: {}: {}", filename, err);
+                        continue;
+                    }
+                };
+
+                if config.compressed_bytes {
+                    if let Some(compressed) = input.compressed {
+                        file_info.num_bytes = compressed.get();
+                    }
+                }
 
                 total.add(&file_info);
 
                 let file_info_string = gen_file_info_string(filename, file_info, &config);
-                println!("{}", file_info_string);
+                writeln!(out, "{}", file_info_string)?;
+
+                if config.stream {
+                    out.flush()?;
+                }
             }
         }
     }
 
     if config.files.len() > 1 {
-        println!(
+        writeln!(
+            out,
             "{}",
             gen_file_info_string(&String::from("total"), total, &config)
-        );
+        )?;
     }
 
+    out.flush()?;
+
     Ok(())
 }
 
@@ -136,8 +172,9 @@ pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
 
     let mut line = String::new();
 
-    while let Ok(n) = file.read_line(&mut line) {
-        if n <= 0 {
+    loop {
+        let n = file.read_line(&mut line)?;
+        if n == 0 {
             break;
         }
 
@@ -151,9 +188,48 @@ pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
     Ok(file_info)
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+struct Input {
+    reader: Box<dyn BufRead>,
+    // Raw bytes read from the source, only set when the input was gzip
+    compressed: Option<Rc<Cell<usize>>>,
+}
+
+struct ByteCounter<R> {
+    inner: R,
+    count: Rc<Cell<usize>>,
+}
+
+impl<R: Read> Read for ByteCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n);
+        Ok(n)
+    }
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+fn open(filename: &str, force_gzip: bool) -> MyResult<Input> {
+    let source: Box<dyn Read> = match filename {
+        "-" => Box::new(io::stdin()),
+        _ => Box::new(File::open(filename)?),
+    };
+
+    let count = Rc::new(Cell::new(0));
+    let mut reader = BufReader::new(ByteCounter {
+        inner: source,
+        count: Rc::clone(&count),
+    });
+
+    if force_gzip || reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Input {
+            reader: Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+            compressed: Some(count),
+        })
+    } else {
+        Ok(Input {
+            reader: Box::new(reader),
+            compressed: None,
+        })
     }
 }
@@ -2,6 +2,11 @@ use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::process::{self, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -9,6 +14,8 @@ const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const ATLAMAL_GZ: &str = "tests/inputs/atlamal.txt.gz";
+const CORRUPT_GZ: &str = "tests/inputs/corrupt.txt.gz";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -211,3 +218,123 @@ fn test_all_words_lines() -> TestResult {
 fn test_all_bytes_lines() -> TestResult {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn atlamal_gzip() -> TestResult {
+    let expected =
+        fs::read_to_string("tests/expected/atlamal.txt.out")?.replace(ATLAMAL, ATLAMAL_GZ);
+    Command::cargo_bin(PRG)?
+        .arg(ATLAMAL_GZ)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_gzip_stdin() -> TestResult {
+    let input = fs::read(ATLAMAL_GZ)?;
+    let expected = fs::read_to_string("tests/expected/atlamal.txt.stdin.out")?;
+    Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn corrupt_gzip() -> TestResult {
+    // The bad file is reported and left out, the others are still counted
+    let expected = fs::read_to_string("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args([CORRUPT_GZ, FOX])
+        .assert()
+        .success()
+        .stdout(format!("{}{}", expected, expected.replace(FOX, "total")))
+        .stderr(predicate::str::starts_with(format!("wcr: {}: ", CORRUPT_GZ)));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn gzip_forced() -> TestResult {
+    // Gzip data is found by its magic bytes, whatever the file is called
+    let input = fs::read(ATLAMAL_GZ)?;
+    let expected = fs::read_to_string("tests/expected/atlamal.txt.stdin.out")?;
+    Command::cargo_bin(PRG)?
+        .arg("--gzip")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+
+    // Plain text read as gzip is an error for that file only
+    Command::cargo_bin(PRG)?
+        .args(["--gzip", FOX])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(predicate::str::starts_with(format!("wcr: {}: ", FOX)));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_gzip_compressed_bytes() -> TestResult {
+    let size = fs::metadata(ATLAMAL_GZ)?.len();
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--compressed-bytes", ATLAMAL_GZ])
+        .assert()
+        .success()
+        .stdout(format!("{:>8} {}\n", size, ATLAMAL_GZ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn compressed_bytes_ignores_plain_input() -> TestResult {
+    run(
+        &["-c", "--compressed-bytes", ATLAMAL],
+        "tests/expected/atlamal.txt.c.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn stream_flushes_before_slow_input() -> TestResult {
+    let fifo = std::env::temp_dir().join(format!("wcr-{}", gen_bad_file()));
+    assert!(process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()?
+        .success());
+
+    let mut child = process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["--stream", FOX])
+        .arg(&fifo)
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // The FIFO has no writer yet, so wcr is still blocked on it while the
+    // first row is waited for. Without --stream that row stays buffered.
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut first = String::new();
+        let _ = stdout.read_line(&mut first);
+        let _ = tx.send(first);
+        // Keeps reading so that the later rows have somewhere to go
+        let _ = io::copy(&mut stdout, &mut io::sink());
+    });
+    let first = rx.recv_timeout(Duration::from_secs(10));
+
+    fs::write(&fifo, "")?;
+    assert!(child.wait()?.success());
+    fs::remove_file(&fifo)?;
+    assert_eq!(first?, fs::read_to_string("tests/expected/fox.txt.out")?);
+    Ok(())
+}
@@ -1,16 +1,204 @@
 use crate::EntryType::*;
+use chrono::{DateTime, Local};
 use clap::{ArgAction, Parser, ValueEnum};
-use regex::Regex;
-use std::{cmp::Ordering, error::Error};
-use walkdir::{DirEntry, WalkDir};
+use globset::{Glob, GlobMatcher};
+use ignore::{WalkBuilder, WalkState};
+use regex::{Regex, RegexBuilder};
+use std::{
+    cmp::Ordering,
+    error::Error,
+    ffi::OsStr,
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc,
+    thread,
+    time::SystemTime,
+};
+use walkdir::WalkDir;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+// The outcome of a search that ran to the end: a failed --exec or --delete,
+// or --quit-on-first without a match, is a failure
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Success,
+    Failure,
+}
+
+impl Status {
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Success => 0,
+            Status::Failure => 1,
+        }
+    }
+}
+
+// The parts of a directory entry findr needs, shared by the serial walkdir
+// walker and the parallel walker from the ignore crate
+trait Entry {
+    fn path(&self) -> &Path;
+    fn file_name(&self) -> &OsStr;
+    fn file_type(&self) -> fs::FileType;
+    fn depth(&self) -> usize;
+    fn path_is_symlink(&self) -> bool;
+    fn metadata(&self) -> MyResult<fs::Metadata>;
+}
+
+impl Entry for walkdir::DirEntry {
+    fn path(&self) -> &Path {
+        walkdir::DirEntry::path(self)
+    }
+
+    fn file_name(&self) -> &OsStr {
+        walkdir::DirEntry::file_name(self)
+    }
+
+    fn file_type(&self) -> fs::FileType {
+        walkdir::DirEntry::file_type(self)
+    }
+
+    fn depth(&self) -> usize {
+        walkdir::DirEntry::depth(self)
+    }
+
+    fn path_is_symlink(&self) -> bool {
+        walkdir::DirEntry::path_is_symlink(self)
+    }
+
+    fn metadata(&self) -> MyResult<fs::Metadata> {
+        Ok(walkdir::DirEntry::metadata(self)?)
+    }
+}
+
+impl Entry for ignore::DirEntry {
+    fn path(&self) -> &Path {
+        ignore::DirEntry::path(self)
+    }
+
+    fn file_name(&self) -> &OsStr {
+        ignore::DirEntry::file_name(self)
+    }
+
+    // Only stdin entries lack a file type, and those are never walked
+    fn file_type(&self) -> fs::FileType {
+        ignore::DirEntry::file_type(self).expect("entry is not stdin")
+    }
+
+    fn depth(&self) -> usize {
+        ignore::DirEntry::depth(self)
+    }
+
+    fn path_is_symlink(&self) -> bool {
+        ignore::DirEntry::path_is_symlink(self)
+    }
+
+    fn metadata(&self) -> MyResult<fs::Metadata> {
+        Ok(ignore::DirEntry::metadata(self)?)
+    }
+}
+
+// With --follow a link that points nowhere can't be followed, so the walkers
+// only report an error for it. Like find -L, it is listed as the link itself.
+struct DanglingLink {
+    path: PathBuf,
+    depth: usize,
+    metadata: fs::Metadata,
+}
+
+impl DanglingLink {
+    fn new(path: Option<&Path>, depth: usize) -> Option<Self> {
+        let path = path?;
+        let metadata = fs::symlink_metadata(path).ok()?;
+        if !metadata.file_type().is_symlink() || fs::metadata(path).is_ok() {
+            return None;
+        }
+        Some(DanglingLink {
+            path: path.to_path_buf(),
+            depth,
+            metadata,
+        })
+    }
+
+    // The ignore crate nests the path and depth of an error in either order
+    fn from_ignore_error(err: &ignore::Error) -> Option<Self> {
+        fn find(err: &ignore::Error, path: Option<&Path>, depth: usize) -> Option<DanglingLink> {
+            match err {
+                ignore::Error::WithPath { path, err } => find(err, Some(path), depth),
+                ignore::Error::WithDepth { depth, err } => find(err, path, *depth),
+                ignore::Error::Io(_) => DanglingLink::new(path, depth),
+                _ => None,
+            }
+        }
+        find(err, None, 0)
+    }
+}
+
+impl Entry for DanglingLink {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    fn file_type(&self) -> fs::FileType {
+        self.metadata.file_type()
+    }
+
+    fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn path_is_symlink(&self) -> bool {
+        true
+    }
+
+    fn metadata(&self) -> MyResult<fs::Metadata> {
+        Ok(self.metadata.clone())
+    }
+}
+
+// Lets the parallel walker send its entries and dangling links down one channel
+impl Entry for Box<dyn Entry + Send> {
+    fn path(&self) -> &Path {
+        (**self).path()
+    }
+
+    fn file_name(&self) -> &OsStr {
+        (**self).file_name()
+    }
+
+    fn file_type(&self) -> fs::FileType {
+        (**self).file_type()
+    }
+
+    fn depth(&self) -> usize {
+        (**self).depth()
+    }
+
+    fn path_is_symlink(&self) -> bool {
+        (**self).path_is_symlink()
+    }
+
+    fn metadata(&self) -> MyResult<fs::Metadata> {
+        (**self).metadata()
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 pub enum EntryType {
     Dir,
     File,
     Link,
+    Socket,
+    Fifo,
+    BlockDevice,
+    CharDevice,
 }
 
 #[derive(Clone, Debug)]
@@ -21,18 +209,123 @@ struct SizeFilter {
 }
 
 impl SizeFilter {
-    fn get_size_in_bytes(&self) -> usize {
+    fn get_unit_in_bytes(&self) -> usize {
         match self.unit {
-            SizeUnit::Bytes => self.size,
-            SizeUnit::Kilobytes => self.size * 1024,
-            SizeUnit::Megabytes => self.size * 1024 * 1024,
-            SizeUnit::Gigabytes => self.size * 1024 * 1024 * 1024,
-            SizeUnit::Terabytes => self.size * 1024 * 1024 * 1024 * 1024,
-            SizeUnit::Petabytes => self.size * 1024 * 1024 * 1024 * 1024 * 1024,
+            SizeUnit::Bytes => 1,
+            SizeUnit::Kilobytes => 1024,
+            SizeUnit::Megabytes => 1024 * 1024,
+            SizeUnit::Gigabytes => 1024 * 1024 * 1024,
+            SizeUnit::Terabytes => 1024 * 1024 * 1024 * 1024,
+            SizeUnit::Petabytes => 1024 * 1024 * 1024 * 1024 * 1024,
+        }
+    }
+
+    // Like find, the file size is rounded up to whole units before comparing,
+    // so 1k matches 1 to 1024 bytes and -1k only empty files
+    fn matches(&self, file_size: usize) -> bool {
+        let size_in_units = file_size.div_ceil(self.get_unit_in_bytes());
+        size_in_units.cmp(&self.size) == self.ordering
+    }
+}
+
+#[derive(Clone, Debug)]
+struct AgeFilter {
+    ordering: Ordering,
+    age: u64,
+}
+
+impl AgeFilter {
+    // Like find, the age is counted in whole units, discarding any remainder
+    fn matches(&self, modified: SystemTime, unit_secs: u64) -> bool {
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .map_or(0, |d| d.as_secs() / unit_secs);
+        age.cmp(&self.age) == self.ordering
+    }
+}
+
+#[derive(Debug)]
+enum Predicate {
+    Type(Vec<EntryType>),
+    Name(Vec<Regex>, Vec<GlobMatcher>),
+    Path(Vec<Regex>),
+    Size(SizeFilter),
+    Empty,
+    Age(AgeFilter, u64),
+    Newer(SystemTime),
+    Owner(Option<u32>, Option<u32>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    fn matches(&self, entry: &impl Entry) -> bool {
+        match self {
+            Predicate::Type(entry_types) => entry_types
+                .iter()
+                .any(|entry_type| is_entry_type(entry.file_type(), *entry_type)),
+            Predicate::Name(patterns, globs) => {
+                let name = entry.file_name().to_string_lossy();
+                patterns.iter().any(|re| re.is_match(&name))
+                    || globs.iter().any(|glob| glob.is_match(&*name))
+            }
+            Predicate::Path(patterns) => {
+                let path = entry.path().to_string_lossy();
+                patterns.iter().any(|re| re.is_match(&path))
+            }
+            Predicate::Size(filter) => {
+                metadata(entry).is_some_and(|metadata| filter.matches(metadata.len() as usize))
+            }
+            Predicate::Empty => {
+                if entry.file_type().is_dir() {
+                    match fs::read_dir(entry.path()) {
+                        Ok(mut contents) => contents.next().is_none(),
+                        Err(e) => {
+                            eprintln!("{}: {}", entry.path().display(), e);
+                            false
+                        }
+                    }
+                } else {
+                    entry.file_type().is_file()
+                        && metadata(entry).is_some_and(|metadata| metadata.len() == 0)
+                }
+            }
+            Predicate::Age(filter, unit_secs) => {
+                modified(entry).is_some_and(|modified| filter.matches(modified, *unit_secs))
+            }
+            Predicate::Newer(newer) => modified(entry).is_some_and(|modified| modified > *newer),
+            Predicate::Owner(user, group) => {
+                metadata(entry).is_some_and(|metadata| owner_matches(&metadata, *user, *group))
+            }
+            Predicate::Not(predicate) => !predicate.matches(entry),
+        }
+    }
+}
+
+// With no predicates at all every entry matches
+fn matches_all(predicates: &[Predicate], any: bool, entry: &impl Entry) -> bool {
+    if predicates.is_empty() {
+        true
+    } else if any {
+        predicates.iter().any(|predicate| predicate.matches(entry))
+    } else {
+        predicates.iter().all(|predicate| predicate.matches(entry))
+    }
+}
+
+fn metadata(entry: &impl Entry) -> Option<fs::Metadata> {
+    match entry.metadata() {
+        Ok(metadata) => Some(metadata),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
         }
     }
 }
 
+fn modified(entry: &impl Entry) -> Option<SystemTime> {
+    metadata(entry).and_then(|metadata| metadata.modified().ok())
+}
+
 #[derive(Debug, ValueEnum, Clone)]
 enum SizeUnit {
     Bytes,
@@ -48,12 +341,36 @@ pub struct Config {
     #[arg(help = "Search paths", required = false, default_value = ".", action = ArgAction::Append)]
     paths: Vec<String>,
 
-    #[arg(help = "Name", required = false, short = 'n', long = "name", value_parser = parse_name, num_args(1..))]
+    #[arg(help = "Name", required = false, short = 'n', long = "name", value_parser = |s: &str| parse_pattern(s, "name", false), num_args(1..))]
     names: Vec<Regex>,
 
-    #[arg(help = "Entry type", value_enum, short = 't', long = "type", value_parser = parse_entry_type, num_args(1..))]
+    #[arg(help = "Name, ignoring case", required = false, long = "iname", value_parser = |s: &str| parse_pattern(s, "iname", true), num_args(1..))]
+    inames: Vec<Regex>,
+
+    #[arg(help = "Name, as a shell glob like '*.rs'", short = 'g', long = "glob", value_parser = |s: &str| parse_glob(s, "glob"), num_args(1..))]
+    globs: Vec<GlobMatcher>,
+
+    #[arg(help = "Whole path", long = "path", visible_alias = "full-path", value_parser = |s: &str| parse_pattern(s, "path", false), num_args(1..))]
+    path_patterns: Vec<Regex>,
+
+    #[arg(help = "Whole path, ignoring case", long = "ipath", value_parser = |s: &str| parse_pattern(s, "ipath", true), num_args(1..))]
+    ipath_patterns: Vec<Regex>,
+
+    #[arg(help = "Entry type: f, d, l, or on Unix s (socket), p (FIFO), b (block device), c (char device)", value_enum, short = 't', long = "type", value_parser = parse_entry_type, num_args(1..))]
     entry_types: Vec<EntryType>,
 
+    #[arg(help = "Name does not match", long = "not-name", value_parser = |s: &str| parse_pattern(s, "not-name", false), num_args(1..))]
+    not_names: Vec<Regex>,
+
+    #[arg(help = "Whole path does not match", long = "not-path", value_parser = |s: &str| parse_pattern(s, "not-path", false), num_args(1..))]
+    not_path_patterns: Vec<Regex>,
+
+    #[arg(help = "Entry type is not", long = "not-type", value_parser = parse_entry_type, num_args(1..))]
+    not_entry_types: Vec<EntryType>,
+
+    #[arg(help = "Match entries passing any filter instead of all of them", long)]
+    any: bool,
+
     #[arg(help = "Descend at most this levels", long = "max-depth")]
     max_depth: Option<usize>,
 
@@ -65,16 +382,214 @@ pub struct Config {
 
     #[arg(help = "filter on size", long, value_parser = parse_size)]
     size: Option<SizeFilter>,
+
+    #[arg(
+        help = "Run a command for each match, {} is replaced by the path; end with ';', or '+' as the last argument to pass all matches at once",
+        long,
+        value_name = "CMD",
+        num_args(1..),
+        value_terminator = ";",
+        allow_hyphen_values = true
+    )]
+    exec: Vec<String>,
+
+    #[arg(help = "Print matches even when --exec is given", long)]
+    print: bool,
+
+    #[arg(
+        help = "Separate matches with NUL instead of newline",
+        short = '0',
+        long
+    )]
+    print0: bool,
+
+    #[arg(
+        help = "Print each match using FORMAT: %p path, %f name, %s size, %d depth, %y type, %T@ or %TY, %Tm, %Td, %TH, %TM, %TS modified time, %% and \\t, \\n escapes",
+        long,
+        value_name = "FORMAT",
+        value_parser = parse_format,
+        conflicts_with = "ls"
+    )]
+    printf: Option<Format>,
+
+    #[arg(
+        help = "Print only the number of matches",
+        long,
+        conflicts_with_all = ["stats", "ls", "printf"]
+    )]
+    count: bool,
+
+    #[arg(
+        help = "Print a summary of the matches instead of listing them",
+        long,
+        conflicts_with_all = ["ls", "printf"]
+    )]
+    stats: bool,
+
+    #[arg(help = "Only empty files and directories", long)]
+    empty: bool,
+
+    #[arg(help = "Modified [+more than|-less than|exactly] N days ago", long, value_parser = parse_age, allow_hyphen_values = true)]
+    mtime: Option<AgeFilter>,
+
+    #[arg(help = "Modified [+more than|-less than|exactly] N minutes ago", long, value_parser = parse_age, allow_hyphen_values = true)]
+    mmin: Option<AgeFilter>,
+
+    #[arg(help = "Modified more recently than FILE", long, value_name = "FILE", value_parser = parse_newer)]
+    newer: Option<SystemTime>,
+
+    #[arg(help = "Skip directories whose name matches this glob, and everything below them", long = "exclude", visible_alias = "prune", value_parser = |s: &str| parse_glob(s, "exclude"), num_args(1..))]
+    excludes: Vec<GlobMatcher>,
+
+    #[arg(
+        help = "Skip paths ignored by .gitignore and .ignore files, and hidden files",
+        long
+    )]
+    gitignore: bool,
+
+    #[arg(
+        help = "Include hidden files with --gitignore",
+        long,
+        requires = "gitignore"
+    )]
+    hidden: bool,
+
+    #[arg(help = "Owned by this user name or uid", long, value_name = "USER", value_parser = parse_user)]
+    user: Option<u32>,
+
+    #[arg(help = "Owned by this group name or gid", long, value_name = "GROUP", value_parser = parse_group)]
+    group: Option<u32>,
+
+    #[arg(
+        help = "Don't descend into directories on other filesystems",
+        long,
+        visible_alias = "one-file-system"
+    )]
+    xdev: bool,
+
+    #[arg(help = "Follow symbolic links", short = 'L', long)]
+    follow: bool,
+
+    #[arg(
+        help = "Stop after N matches",
+        long,
+        value_name = "N",
+        conflicts_with = "quit_on_first"
+    )]
+    max_results: Option<usize>,
+
+    #[arg(
+        help = "Stop at the first match, exiting with 1 when there is none",
+        long
+    )]
+    quit_on_first: bool,
+
+    #[arg(
+        help = "Walk directories with this many threads, 0 picks a number automatically",
+        long,
+        conflicts_with_all = ["delete", "depth"]
+    )]
+    threads: Option<usize>,
+
+    #[arg(help = "Sort matches by path", long)]
+    sort: bool,
+
+    #[arg(
+        help = "List directories after their contents, as needed when piping into xargs rm -r",
+        short = 'd',
+        long
+    )]
+    depth: bool,
+
+    #[arg(help = "List matches like ls -l", long)]
+    ls: bool,
+
+    #[arg(help = "Delete matches, directories after their contents", long)]
+    delete: bool,
+
+    #[arg(help = "Allow --delete without any filter", long)]
+    force: bool,
+
+    #[arg(help = "Report each deleted entry", short = 'v', long)]
+    verbose: bool,
+}
+
+impl Config {
+    fn predicates(&self) -> Vec<Predicate> {
+        let mut predicates = vec![];
+
+        if !self.entry_types.is_empty() {
+            predicates.push(Predicate::Type(self.entry_types.clone()));
+        }
+
+        if !self.names.is_empty() || !self.inames.is_empty() || !self.globs.is_empty() {
+            let patterns = self.names.iter().chain(&self.inames).cloned().collect();
+            predicates.push(Predicate::Name(patterns, self.globs.clone()));
+        }
+
+        if !self.path_patterns.is_empty() || !self.ipath_patterns.is_empty() {
+            let patterns = self
+                .path_patterns
+                .iter()
+                .chain(&self.ipath_patterns)
+                .cloned()
+                .collect();
+            predicates.push(Predicate::Path(patterns));
+        }
+
+        if let Some(size) = &self.size {
+            predicates.push(Predicate::Size(size.clone()));
+        }
+
+        if self.empty {
+            predicates.push(Predicate::Empty);
+        }
+
+        if let Some(mtime) = &self.mtime {
+            predicates.push(Predicate::Age(mtime.clone(), 24 * 60 * 60));
+        }
+
+        if let Some(mmin) = &self.mmin {
+            predicates.push(Predicate::Age(mmin.clone(), 60));
+        }
+
+        if let Some(newer) = self.newer {
+            predicates.push(Predicate::Newer(newer));
+        }
+
+        if self.user.is_some() || self.group.is_some() {
+            predicates.push(Predicate::Owner(self.user, self.group));
+        }
+
+        if !self.not_names.is_empty() {
+            let name = Predicate::Name(self.not_names.clone(), vec![]);
+            predicates.push(Predicate::Not(Box::new(name)));
+        }
+
+        if !self.not_path_patterns.is_empty() {
+            let path = Predicate::Path(self.not_path_patterns.clone());
+            predicates.push(Predicate::Not(Box::new(path)));
+        }
+
+        if !self.not_entry_types.is_empty() {
+            let entry_type = Predicate::Type(self.not_entry_types.clone());
+            predicates.push(Predicate::Not(Box::new(entry_type)));
+        }
+
+        predicates
+    }
 }
 
 fn parse_size(input: &str) -> Result<SizeFilter, String> {
-    let re = Regex::new(r"([+-]?)(\d+)([ckMGTP]?)").unwrap();
-    let caps = re.captures(input).unwrap();
+    let re = Regex::new(r"^([+-]?)(\d+)([ckMGTP]?)$").unwrap();
+    let caps = re
+        .captures(input)
+        .ok_or_else(|| format!("illegal size \"{}\"", input))?;
 
     Ok(SizeFilter {
-        ordering: parse_ordering(&caps[1]).unwrap(),
-        size: parse_size_value(&caps[2]).unwrap(),
-        unit: parse_unit(&caps[3]).unwrap(),
+        ordering: parse_ordering(&caps[1])?,
+        size: parse_size_value(&caps[2])?,
+        unit: parse_unit(&caps[3])?,
     })
 }
 
@@ -83,10 +598,30 @@ fn parse_ordering(input: &str) -> Result<Ordering, String> {
         "+" => Ok(Ordering::Greater),
         "-" => Ok(Ordering::Less),
         "" => Ok(Ordering::Equal),
-        _ => Err(format!("illegal ordering option")),
+        _ => Err("illegal ordering option".to_string()),
     }
 }
 
+fn parse_age(input: &str) -> Result<AgeFilter, String> {
+    let re = Regex::new(r"^([+-]?)(\d+)$").unwrap();
+    let caps = re
+        .captures(input)
+        .ok_or_else(|| format!("illegal age \"{}\"", input))?;
+
+    Ok(AgeFilter {
+        ordering: parse_ordering(&caps[1])?,
+        age: caps[2]
+            .parse()
+            .map_err(|_| "illegal age value".to_string())?,
+    })
+}
+
+fn parse_newer(file: &str) -> Result<SystemTime, String> {
+    fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| format!("{}: {}", file, e))
+}
+
 fn parse_unit(input: &str) -> Result<SizeUnit, String> {
     match input {
         "c" => Ok(SizeUnit::Bytes),
@@ -94,25 +629,115 @@ fn parse_unit(input: &str) -> Result<SizeUnit, String> {
         "k" => Ok(SizeUnit::Kilobytes),
         "M" => Ok(SizeUnit::Megabytes),
         "G" => Ok(SizeUnit::Gigabytes),
+        "T" => Ok(SizeUnit::Terabytes),
         "P" => Ok(SizeUnit::Petabytes),
-        _ => Err(format!("illegal unit")),
+        _ => Err("illegal unit".to_string()),
     }
 }
 
 fn parse_size_value(input: &str) -> Result<usize, String> {
-    input.parse().map_err(|_e| format!("illegal size value"))
+    input.parse().map_err(|_e| "illegal size value".to_string())
+}
+
+fn parse_pattern(pattern: &str, flag: &str, insensitive: bool) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(insensitive)
+        .build()
+        .map_err(|_| format!("Invalid --{} \"{}\"", flag, pattern))
+}
+
+fn parse_glob(glob: &str, flag: &str) -> Result<GlobMatcher, String> {
+    Glob::new(glob)
+        .map(|glob| glob.compile_matcher())
+        .map_err(|_| format!("Invalid --{} \"{}\"", flag, glob))
+}
+
+#[cfg(unix)]
+fn parse_user(user: &str) -> Result<u32, String> {
+    user.parse().or_else(|_| {
+        users::get_user_by_name(user)
+            .map(|user| user.uid())
+            .ok_or_else(|| format!("Unknown user \"{}\"", user))
+    })
+}
+
+#[cfg(unix)]
+fn parse_group(group: &str) -> Result<u32, String> {
+    group.parse().or_else(|_| {
+        users::get_group_by_name(group)
+            .map(|group| group.gid())
+            .ok_or_else(|| format!("Unknown group \"{}\"", group))
+    })
+}
+
+#[cfg(not(unix))]
+fn parse_user(_user: &str) -> Result<u32, String> {
+    Err("--user is only supported on Unix".to_string())
+}
+
+#[cfg(not(unix))]
+fn parse_group(_group: &str) -> Result<u32, String> {
+    Err("--group is only supported on Unix".to_string())
+}
+
+#[cfg(unix)]
+fn owner_matches(metadata: &fs::Metadata, user: Option<u32>, group: Option<u32>) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    user.is_none_or(|uid| metadata.uid() == uid) && group.is_none_or(|gid| metadata.gid() == gid)
 }
 
-fn parse_name(name: &str) -> Result<Regex, String> {
-    Regex::new(&name).map_err(|_| format!("Invalid --name \"{}\"", name))
+#[cfg(not(unix))]
+fn owner_matches(_metadata: &fs::Metadata, _user: Option<u32>, _group: Option<u32>) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn is_entry_type(file_type: fs::FileType, entry_type: EntryType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    match entry_type {
+        File => file_type.is_file(),
+        Dir => file_type.is_dir(),
+        Link => file_type.is_symlink(),
+        Socket => file_type.is_socket(),
+        Fifo => file_type.is_fifo(),
+        BlockDevice => file_type.is_block_device(),
+        CharDevice => file_type.is_char_device(),
+    }
+}
+
+#[cfg(not(unix))]
+fn is_entry_type(file_type: fs::FileType, entry_type: EntryType) -> bool {
+    match entry_type {
+        File => file_type.is_file(),
+        Dir => file_type.is_dir(),
+        Link => file_type.is_symlink(),
+        Socket | Fifo | BlockDevice | CharDevice => false,
+    }
+}
+
+#[cfg(unix)]
+fn parse_entry_type(str: &str) -> Result<EntryType, String> {
+    match str {
+        "f" => Ok(File),
+        "d" => Ok(Dir),
+        "l" => Ok(Link),
+        "s" => Ok(Socket),
+        "p" => Ok(Fifo),
+        "b" => Ok(BlockDevice),
+        "c" => Ok(CharDevice),
+        _ => Err("[possible values: b, c, d, f, l, p, s]".to_string()),
+    }
 }
 
+#[cfg(not(unix))]
 fn parse_entry_type(str: &str) -> Result<EntryType, String> {
     match str {
         "f" => Ok(File),
         "d" => Ok(Dir),
         "l" => Ok(Link),
-        _ => Err(format!("[possible values: d, f, l]")),
+        _ => Err("[possible values: d, f, l]".to_string()),
     }
 }
 
@@ -120,75 +745,927 @@ pub fn get_args() -> MyResult<Config> {
     Ok(Config::parse())
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let type_filter = |entry: &DirEntry| {
-        config.entry_types.is_empty()
-            || config
-                .entry_types
-                .iter()
-                .any(|entry_type| match entry_type {
-                    File => entry.file_type().is_file(),
-                    Dir => entry.file_type().is_dir(),
-                    Link => entry.file_type().is_symlink(),
-                })
-    };
+// Runs `cmd` with every "{}" in its arguments replaced by `paths`. Returns
+// false when the command could not be started or exited unsuccessfully.
+fn exec(cmd: &[String], paths: &[String]) -> bool {
+    let args = cmd[1..].iter().flat_map(|arg| {
+        if arg == "{}" {
+            paths.to_vec()
+        } else {
+            vec![arg.replace("{}", &paths.join(" "))]
+        }
+    });
 
-    let name_filter = |entry: &DirEntry| {
-        config.names.is_empty()
-            || config
-                .names
-                .iter()
-                .any(|re| re.is_match(&entry.file_name().to_string_lossy()))
+    match Command::new(&cmd[0]).args(args).status() {
+        Err(e) => {
+            eprintln!("{}: {}", cmd[0], e);
+            false
+        }
+        Ok(status) if !status.success() => {
+            eprintln!("{}: {}", cmd[0], status);
+            false
+        }
+        Ok(_) => true,
+    }
+}
+
+// Root paths are never pruned, only directories found below them
+fn is_excluded(entry: &impl Entry, excludes: &[GlobMatcher]) -> bool {
+    entry.depth() > 0
+        && entry.file_type().is_dir()
+        && excludes.iter().any(|glob| glob.is_match(entry.file_name()))
+}
+
+#[cfg(unix)]
+fn device(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+fn is_other_device(device: Option<u64>, root_device: Option<u64>) -> bool {
+    matches!((device, root_device), (Some(device), Some(root)) if device != root)
+}
+
+// Only directories are checked, as those are the only mount points worth pruning,
+// and none are without --xdev, which sets the root device
+fn is_mount_point(entry: &impl Entry, root_device: Option<u64>) -> bool {
+    root_device.is_some()
+        && entry.depth() > 0
+        && entry.file_type().is_dir()
+        && is_other_device(entry.metadata().ok().as_ref().and_then(device), root_device)
+}
+
+fn type_char(file_type: fs::FileType) -> char {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_fifo() {
+            return 'p';
+        } else if file_type.is_socket() {
+            return 's';
+        } else if file_type.is_block_device() {
+            return 'b';
+        } else if file_type.is_char_device() {
+            return 'c';
+        }
+    }
+
+    if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else {
+        '-'
+    }
+}
+
+// Formats permission bits like ls, e.g. 0o4755 as "rwsr-xr-x"
+fn format_mode(mode: u32) -> String {
+    let mut perms = String::with_capacity(9);
+
+    for (shift, special, exec_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        perms.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        perms.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        perms.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, false) => 'x',
+            (false, false) => '-',
+            (true, true) => exec_char,
+            (false, true) => exec_char.to_ascii_uppercase(),
+        });
+    }
+
+    perms
+}
+
+#[cfg(unix)]
+fn owner_columns(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+
+    let user = users::get_user_by_uid(metadata.uid()).map_or_else(
+        || metadata.uid().to_string(),
+        |user| user.name().to_string_lossy().to_string(),
+    );
+    let group = users::get_group_by_gid(metadata.gid()).map_or_else(
+        || metadata.gid().to_string(),
+        |group| group.name().to_string_lossy().to_string(),
+    );
+
+    format!(" {:<8} {:<8}", user, group)
+}
+
+#[cfg(not(unix))]
+fn owner_columns(_metadata: &fs::Metadata) -> String {
+    String::new()
+}
+
+#[cfg(unix)]
+fn mode_and_links(metadata: &fs::Metadata) -> (u32, u64) {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.mode(), metadata.nlink())
+}
+
+#[cfg(not(unix))]
+fn mode_and_links(metadata: &fs::Metadata) -> (u32, u64) {
+    let mode = if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o666
     };
+    (mode, 1)
+}
+
+fn long_listing(entry: &impl Entry) -> Option<String> {
+    let metadata = metadata(entry)?;
+    let (mode, links) = mode_and_links(&metadata);
+    let modified: DateTime<Local> = metadata.modified().ok()?.into();
+
+    let mut row = format!(
+        "{}{} {:>3}{} {:>8} {} {}",
+        type_char(entry.file_type()),
+        format_mode(mode),
+        links,
+        owner_columns(&metadata),
+        metadata.len(),
+        modified.format("%Y-%m-%d %H:%M"),
+        entry.path().display()
+    );
+
+    if entry.path_is_symlink() {
+        if let Ok(target) = fs::read_link(entry.path()) {
+            row.push_str(&format!(" -> {}", target.display()));
+        }
+    }
 
-    let size_filter = |entry: &DirEntry| {
-        if let Some(size_config) = &config.size {
-            let file_size = entry.metadata().unwrap().len() as usize;
-            let size_in_filter = size_config.get_size_in_bytes();
-            match size_config.ordering {
-                Ordering::Equal => file_size == size_in_filter,
-                Ordering::Less => file_size < size_in_filter,
-                Ordering::Greater => file_size > size_in_filter,
+    Some(row)
+}
+
+// One piece of a --printf format string
+#[derive(Clone, Debug, PartialEq)]
+enum Directive {
+    Literal(String),
+    Path,
+    Name,
+    Size,
+    Depth,
+    Type,
+    ModifiedEpoch,
+    Modified(char),
+}
+
+// A parsed --printf format string
+#[derive(Clone, Debug, PartialEq)]
+struct Format(Vec<Directive>);
+
+fn parse_format(format: &str) -> Result<Format, String> {
+    let mut directives = vec![];
+    let mut literal = String::new();
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        let directive = match c {
+            '\\' => {
+                match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('0') => literal.push('\0'),
+                    Some('\\') => literal.push('\\'),
+                    Some(c) => return Err(format!("unknown escape \"\\{}\"", c)),
+                    None => literal.push('\\'),
+                }
+                continue;
             }
-        } else {
-            true
+            '%' => match chars.next() {
+                Some('%') => {
+                    literal.push('%');
+                    continue;
+                }
+                Some('p') => Directive::Path,
+                Some('f') => Directive::Name,
+                Some('s') => Directive::Size,
+                Some('d') => Directive::Depth,
+                Some('y') => Directive::Type,
+                Some('T') => match chars.next() {
+                    Some('@') => Directive::ModifiedEpoch,
+                    Some(c @ ('Y' | 'm' | 'd' | 'H' | 'M' | 'S')) => Directive::Modified(c),
+                    Some(c) => return Err(format!("unknown directive \"%T{}\"", c)),
+                    None => return Err("unknown directive \"%T\"".to_string()),
+                },
+                Some(c) => return Err(format!("unknown directive \"%{}\"", c)),
+                None => return Err("format ends with a lone \"%\"".to_string()),
+            },
+            c => {
+                literal.push(c);
+                continue;
+            }
+        };
+
+        if !literal.is_empty() {
+            directives.push(Directive::Literal(std::mem::take(&mut literal)));
         }
+        directives.push(directive);
+    }
+
+    if !literal.is_empty() {
+        directives.push(Directive::Literal(literal));
+    }
+
+    Ok(Format(directives))
+}
+
+// Renders `entry` according to a parsed --printf format
+fn format_entry(entry: &impl Entry, format: &Format) -> Option<String> {
+    let directives = &format.0;
+    let needs_metadata = directives.iter().any(|directive| {
+        matches!(
+            directive,
+            Directive::Size | Directive::ModifiedEpoch | Directive::Modified(_)
+        )
+    });
+    let metadata = if needs_metadata {
+        Some(metadata(entry)?)
+    } else {
+        None
     };
+    let modified = || -> Option<SystemTime> { metadata.as_ref()?.modified().ok() };
 
-    for path in &config.paths {
-        let mut walkdir = WalkDir::new(path);
+    let mut out = String::new();
+    for directive in directives {
+        match directive {
+            Directive::Literal(text) => out.push_str(text),
+            Directive::Path => out.push_str(&entry.path().to_string_lossy()),
+            Directive::Name => out.push_str(&entry.file_name().to_string_lossy()),
+            Directive::Size => out.push_str(&metadata.as_ref()?.len().to_string()),
+            Directive::Depth => out.push_str(&entry.depth().to_string()),
+            // Use the --type letters, so regular files are "f" rather than "-"
+            Directive::Type => out.push(match type_char(entry.file_type()) {
+                '-' => 'f',
+                c => c,
+            }),
+            Directive::ModifiedEpoch => {
+                let modified: DateTime<Local> = modified()?.into();
+                out.push_str(&modified.timestamp().to_string());
+            }
+            Directive::Modified(c) => {
+                let modified: DateTime<Local> = modified()?.into();
+                out.push_str(&modified.format(&format!("%{}", c)).to_string());
+            }
+        }
+    }
+
+    Some(out)
+}
+
+fn delete(entry: &impl Entry) -> io::Result<()> {
+    if entry.file_type().is_dir() {
+        fs::remove_dir(entry.path())
+    } else {
+        fs::remove_file(entry.path())
+    }
+}
+
+// Totals over the matched entries for --count and --stats
+#[derive(Debug, Default)]
+struct Stats {
+    matches: usize,
+    total_size: u64,
+    dirs: usize,
+    files: usize,
+    links: usize,
+    max_depth: usize,
+}
+
+impl Stats {
+    fn add(&mut self, entry: &impl Entry) {
+        let file_type = entry.file_type();
 
-        match (config.max_depth, config.min_depth) {
-            (Some(max_depth), Some(min_depth)) if max_depth < min_depth => return Ok(()),
-            (Some(max_depth), Some(min_depth)) => {
-                walkdir = walkdir.min_depth(min_depth).max_depth(max_depth);
+        self.matches += 1;
+        self.max_depth = self.max_depth.max(entry.depth());
+        if file_type.is_dir() {
+            self.dirs += 1;
+        } else if file_type.is_symlink() {
+            self.links += 1;
+        } else if file_type.is_file() {
+            self.files += 1;
+            if let Some(metadata) = metadata(entry) {
+                self.total_size += metadata.len();
             }
-            (Some(max_depth), None) => {
-                walkdir = walkdir.max_depth(max_depth);
+        }
+    }
+
+    fn report(&self) -> String {
+        format!(
+            "Matches: {}\nTotal size: {}\nDirectories: {}\nFiles: {}\nLinks: {}\nMax depth: {}\n",
+            self.matches, self.total_size, self.dirs, self.files, self.links, self.max_depth
+        )
+    }
+}
+
+// Prints, runs --exec for, or deletes each matched entry
+struct Actions<'a> {
+    config: &'a Config,
+    out: BufWriter<io::StdoutLock<'static>>,
+    exec_cmd: &'a [String],
+    batch: bool,
+    batched: Vec<String>,
+    print: bool,
+    separator: char,
+    failed: bool,
+    limit: Option<usize>,
+    handled: usize,
+    stats: Option<Stats>,
+}
+
+impl<'a> Actions<'a> {
+    fn new(config: &'a Config) -> Self {
+        let batch = matches!(config.exec.as_slice(), [_, .., last] if last == "+");
+        let exec_cmd = if batch {
+            &config.exec[..config.exec.len() - 1]
+        } else {
+            &config.exec[..]
+        };
+
+        Actions {
+            config,
+            out: BufWriter::new(io::stdout().lock()),
+            exec_cmd,
+            batch,
+            batched: vec![],
+            print: ((exec_cmd.is_empty() && !config.delete) || config.print)
+                && !config.count
+                && !config.stats,
+            separator: if config.print0 { '\0' } else { '\n' },
+            failed: false,
+            limit: if config.quit_on_first {
+                Some(1)
+            } else {
+                config.max_results
+            },
+            handled: 0,
+            stats: (config.count || config.stats).then(Stats::default),
+        }
+    }
+
+    // Whether --max-results has been reached and the walk should stop
+    fn done(&self) -> bool {
+        self.limit.is_some_and(|limit| self.handled >= limit)
+    }
+
+    fn handle(&mut self, entry: &impl Entry) -> MyResult<()> {
+        self.handled += 1;
+        if let Some(stats) = &mut self.stats {
+            stats.add(entry);
+        }
+
+        let path = entry.path().display().to_string();
+
+        if let Some(format) = &self.config.printf {
+            if let Some(text) = format_entry(entry, format) {
+                write!(self.out, "{}", text)?;
             }
-            (None, Some(min_depth)) => {
-                walkdir = walkdir.min_depth(min_depth);
+        } else if self.print {
+            if self.config.ls {
+                if let Some(row) = long_listing(entry) {
+                    write!(self.out, "{}{}", row, self.separator)?;
+                }
+            } else {
+                write!(self.out, "{}{}", path, self.separator)?;
             }
-            _ => (),
         }
 
-        let entries = walkdir
-            .into_iter()
-            .filter_map(|e| match e {
+        if self.batch {
+            self.batched.push(path.clone());
+        } else if !self.exec_cmd.is_empty() {
+            self.out.flush()?;
+            self.failed |= !exec(self.exec_cmd, std::slice::from_ref(&path));
+        }
+
+        if self.config.delete {
+            match delete(entry) {
                 Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    self.failed = true;
+                }
+                Ok(_) if self.config.verbose => writeln!(self.out, "removed '{}'", path)?,
+                Ok(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Runs any batched --exec and returns whether every action succeeded and,
+    // with --quit-on-first, something matched
+    fn finish(mut self) -> MyResult<bool> {
+        match &self.stats {
+            Some(stats) if self.config.count => writeln!(self.out, "{}", stats.matches)?,
+            Some(stats) => write!(self.out, "{}", stats.report())?,
+            None => (),
+        }
+        self.out.flush()?;
+
+        if !self.batched.is_empty() {
+            self.failed |= !exec(self.exec_cmd, &self.batched);
+        }
+
+        let nothing_found = self.config.quit_on_first && self.handled == 0;
+        Ok(!self.failed && !nothing_found)
+    }
+}
+
+fn walk(
+    path: &str,
+    root_device: Option<u64>,
+    config: &Config,
+    predicates: &[Predicate],
+    actions: &mut Actions,
+) -> MyResult<()> {
+    let mut walkdir = WalkDir::new(path)
+        .follow_links(config.follow)
+        .contents_first(config.depth || config.delete);
+
+    if let Some(max_depth) = config.max_depth {
+        walkdir = walkdir.max_depth(max_depth);
+    }
+
+    if let Some(min_depth) = config.min_depth {
+        walkdir = walkdir.min_depth(min_depth);
+    }
+
+    if config.sort {
+        walkdir = walkdir.sort_by_file_name();
+    }
+
+    let min_depth = config.min_depth.unwrap_or(0);
+    let entries = walkdir.into_iter().filter_entry(|entry| {
+        !is_excluded(entry, &config.excludes) && !is_mount_point(entry, root_device)
+    });
+
+    for result in entries {
+        let entry: Box<dyn Entry + Send> = match result {
+            Ok(entry) => Box::new(entry),
+            Err(e) => match DanglingLink::new(e.path(), e.depth()) {
+                Some(link) if link.depth >= min_depth => Box::new(link),
+                Some(_) => continue,
+                None => {
                     eprintln!("{}", e);
-                    None
+                    continue;
                 }
-                Ok(entry) => Some(entry),
+            },
+        };
+        if matches_all(predicates, config.any, &entry) {
+            actions.handle(&entry)?;
+            if actions.done() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Filters entries on the walker's threads and funnels matches back to this
+// one, which runs the actions. With --sort, --depth or --delete every match
+// is collected first. This is also the serial walk for --gitignore, with one
+// thread, so that the ignore crate applies the ignore files.
+fn walk_parallel(
+    path: &str,
+    threads: usize,
+    root_device: Option<u64>,
+    config: &Config,
+    predicates: &[Predicate],
+    actions: &mut Actions,
+) -> MyResult<()> {
+    let walker = WalkBuilder::new(path)
+        .standard_filters(false)
+        .git_ignore(config.gitignore)
+        .ignore(config.gitignore)
+        .require_git(false)
+        .hidden(config.gitignore && !config.hidden)
+        .follow_links(config.follow)
+        .max_depth(config.max_depth)
+        .threads(threads)
+        .build_parallel();
+    let min_depth = config.min_depth.unwrap_or(0);
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            walker.run(|| {
+                let tx = tx.clone();
+                Box::new(move |result| {
+                    let entry: Box<dyn Entry + Send> = match result {
+                        Err(e) => match DanglingLink::from_ignore_error(&e) {
+                            Some(link) => Box::new(link),
+                            None => {
+                                eprintln!("{}", e);
+                                return WalkState::Continue;
+                            }
+                        },
+                        Ok(entry) if entry.is_stdin() => return WalkState::Continue,
+                        Ok(entry) => Box::new(entry),
+                    };
+                    if is_excluded(&entry, &config.excludes) || is_mount_point(&entry, root_device)
+                    {
+                        WalkState::Skip
+                    } else if entry.depth() >= min_depth
+                        && matches_all(predicates, config.any, &entry)
+                        && tx.send(entry).is_err()
+                    {
+                        WalkState::Quit
+                    } else {
+                        WalkState::Continue
+                    }
+                })
             })
-            .filter(type_filter)
-            .filter(name_filter)
-            .filter(size_filter)
-            .map(|entry| entry.path().display().to_string())
-            .collect::<Vec<_>>();
+        });
+
+        let mut handle_all = |entries: &mut dyn Iterator<Item = Box<dyn Entry + Send>>| {
+            for entry in entries {
+                actions.handle(&entry)?;
+                if actions.done() {
+                    break;
+                }
+            }
+            Ok(())
+        };
+        let result = if config.depth || config.delete {
+            let mut entries: Vec<_> = rx.iter().collect();
+            entries.sort_by(|a, b| contents_first(a.path(), b.path()));
+            handle_all(&mut entries.into_iter())
+        } else if config.sort {
+            let mut entries: Vec<_> = rx.iter().collect();
+            entries.sort_by(|a, b| a.path().cmp(b.path()));
+            handle_all(&mut entries.into_iter())
+        } else {
+            handle_all(&mut rx.iter())
+        };
+
+        // Stops the walker early if an action failed or --max-results was hit
+        drop(rx);
+        result
+    })
+}
 
-        println!("{}", entries.join("\n"));
+// Sorts a directory after everything below it, as --depth does
+fn contents_first(a: &Path, b: &Path) -> Ordering {
+    if a == b {
+        Ordering::Equal
+    } else if a.starts_with(b) {
+        Ordering::Less
+    } else if b.starts_with(a) {
+        Ordering::Greater
+    } else {
+        a.cmp(b)
     }
+}
 
-    Ok(())
+pub fn run(config: Config) -> MyResult<Status> {
+    let predicates = config.predicates();
+
+    if config.delete && !config.force && predicates.is_empty() {
+        return Err(
+            "Refusing to --delete without a filter, use --force to delete everything".into(),
+        );
+    }
+
+    if let (Some(max_depth), Some(min_depth)) = (config.max_depth, config.min_depth) {
+        if max_depth < min_depth {
+            return Ok(Status::Success);
+        }
+    }
+
+    let mut actions = Actions::new(&config);
+
+    for path in &config.paths {
+        if actions.done() {
+            break;
+        }
+
+        let root_device = if config.xdev {
+            fs::metadata(path).ok().as_ref().and_then(device)
+        } else {
+            None
+        };
+
+        match config.threads {
+            Some(threads) if threads != 1 => walk_parallel(
+                path,
+                threads,
+                root_device,
+                &config,
+                &predicates,
+                &mut actions,
+            )?,
+            _ if config.gitignore => {
+                walk_parallel(path, 1, root_device, &config, &predicates, &mut actions)?
+            }
+            _ => walk(path, root_device, &config, &predicates, &mut actions)?,
+        }
+    }
+
+    if actions.finish()? {
+        Ok(Status::Success)
+    } else {
+        Ok(Status::Failure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("findr-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_is_excluded_prunes_subtree() {
+        let dir = temp_dir("exclude");
+        fs::create_dir_all(dir.join("target/debug/deps")).unwrap();
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("target/debug/deps/lib.rlib"), "").unwrap();
+        fs::write(dir.join("src/main.rs"), "").unwrap();
+
+        let excludes = vec![parse_glob("target", "exclude").unwrap()];
+        let mut visited = 0;
+        let names: Vec<_> = WalkDir::new(&dir)
+            .into_iter()
+            .filter_entry(|entry| {
+                visited += 1;
+                !is_excluded(entry, &excludes)
+            })
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+
+        // The root, src, main.rs and target itself, but nothing below target
+        assert_eq!(visited, 4);
+        assert!(!names.contains(&"target".to_string()));
+        assert!(names.contains(&"main.rs".to_string()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // An entry with made up path and depth whose metadata comes from a real file
+    struct FakeEntry {
+        path: PathBuf,
+        depth: usize,
+        file: PathBuf,
+    }
+
+    impl Entry for FakeEntry {
+        fn path(&self) -> &Path {
+            &self.path
+        }
+
+        fn file_name(&self) -> &OsStr {
+            self.path.file_name().unwrap()
+        }
+
+        fn file_type(&self) -> fs::FileType {
+            fs::symlink_metadata(&self.file).unwrap().file_type()
+        }
+
+        fn depth(&self) -> usize {
+            self.depth
+        }
+
+        fn path_is_symlink(&self) -> bool {
+            false
+        }
+
+        fn metadata(&self) -> MyResult<fs::Metadata> {
+            Ok(fs::metadata(&self.file)?)
+        }
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(
+            parse_format("%p\\t%s%%\\n"),
+            Ok(Format(vec![
+                Directive::Path,
+                Directive::Literal("\t".to_string()),
+                Directive::Size,
+                Directive::Literal("%\n".to_string()),
+            ]))
+        );
+        assert_eq!(
+            parse_format("%TY-%Tm %T@"),
+            Ok(Format(vec![
+                Directive::Modified('Y'),
+                Directive::Literal("-".to_string()),
+                Directive::Modified('m'),
+                Directive::Literal(" ".to_string()),
+                Directive::ModifiedEpoch,
+            ]))
+        );
+        assert_eq!(parse_format(""), Ok(Format(vec![])));
+        assert_eq!(
+            parse_format("%x"),
+            Err("unknown directive \"%x\"".to_string())
+        );
+        assert_eq!(
+            parse_format("%Tq"),
+            Err("unknown directive \"%Tq\"".to_string())
+        );
+        assert!(parse_format("50%").is_err());
+    }
+
+    #[test]
+    fn test_format_entry() {
+        let dir = temp_dir("printf");
+        let file = dir.join("data");
+        fs::write(&file, "hello").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let entry = FakeEntry {
+            path: PathBuf::from("some/where/report.txt"),
+            depth: 3,
+            file,
+        };
+        let format = |format: &str| format_entry(&entry, &parse_format(format).unwrap());
+
+        assert_eq!(
+            format("%p|%f|%s|%d|%y\\n").unwrap(),
+            "some/where/report.txt|report.txt|5|3|f\n"
+        );
+        assert_eq!(format("%T@").unwrap(), "1000000000");
+        let local: DateTime<Local> = modified.into();
+        assert_eq!(
+            format("%TY-%Tm-%Td").unwrap(),
+            local.format("%Y-%m-%d").to_string()
+        );
+        assert_eq!(format("100%%").unwrap(), "100%");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn entry(path: &str) -> walkdir::DirEntry {
+        WalkDir::new(path).into_iter().next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_predicate_matches() {
+        let file = entry("tests/inputs/a/a.txt");
+        let dir = entry("tests/inputs/a");
+
+        let is_file = Predicate::Type(vec![File]);
+        assert!(is_file.matches(&file));
+        assert!(!is_file.matches(&dir));
+
+        let txt = Predicate::Name(vec![Regex::new(r"\.txt$").unwrap()], vec![]);
+        assert!(txt.matches(&file));
+        assert!(!txt.matches(&dir));
+
+        let not_txt = Predicate::Not(Box::new(txt));
+        assert!(!not_txt.matches(&file));
+        assert!(not_txt.matches(&dir));
+
+        let csv = Predicate::Name(vec![], vec![parse_glob("*.csv", "glob").unwrap()]);
+        assert!(!csv.matches(&file));
+        assert!(csv.matches(&entry("tests/inputs/g.csv")));
+    }
+
+    #[test]
+    fn test_matches_all() {
+        let file = entry("tests/inputs/a/a.txt");
+        let dir = entry("tests/inputs/a");
+        let predicates = vec![
+            Predicate::Type(vec![Dir]),
+            Predicate::Path(vec![Regex::new("a/a").unwrap()]),
+        ];
+
+        assert!(matches_all(&[], false, &file));
+        assert!(matches_all(&[], true, &file));
+
+        assert!(!matches_all(&predicates, false, &file));
+        assert!(!matches_all(&predicates, false, &dir));
+
+        assert!(matches_all(&predicates, true, &file));
+        assert!(matches_all(&predicates, true, &dir));
+        assert!(!matches_all(
+            &predicates,
+            true,
+            &entry("tests/inputs/g.csv")
+        ));
+    }
+
+    fn size_in_bytes(filter: &SizeFilter) -> usize {
+        filter.size * filter.get_unit_in_bytes()
+    }
+
+    #[test]
+    fn test_parse_size() {
+        let size = parse_size("10").unwrap();
+        assert_eq!(size.ordering, Ordering::Equal);
+        assert_eq!(size_in_bytes(&size), 10);
+
+        let size = parse_size("+10c").unwrap();
+        assert_eq!(size.ordering, Ordering::Greater);
+        assert_eq!(size_in_bytes(&size), 10);
+
+        let size = parse_size("-2k").unwrap();
+        assert_eq!(size.ordering, Ordering::Less);
+        assert_eq!(size_in_bytes(&size), 2 * 1024);
+
+        assert_eq!(size_in_bytes(&parse_size("1M").unwrap()), 1 << 20);
+        assert_eq!(size_in_bytes(&parse_size("1G").unwrap()), 1 << 30);
+        assert_eq!(size_in_bytes(&parse_size("+1T").unwrap()), 1 << 40);
+        assert_eq!(size_in_bytes(&parse_size("1P").unwrap()), 1 << 50);
+    }
+
+    #[test]
+    fn test_size_filter_rounds_up() {
+        let matches = |filter: &str, size: usize| parse_size(filter).unwrap().matches(size);
+
+        assert!(!matches("1k", 0));
+        assert!(matches("1k", 1));
+        assert!(matches("1k", 1023));
+        assert!(matches("1k", 1024));
+        assert!(!matches("1k", 1025));
+
+        assert!(!matches("+1k", 1023));
+        assert!(!matches("+1k", 1024));
+        assert!(matches("+1k", 1025));
+
+        assert!(matches("-1k", 0));
+        assert!(!matches("-1k", 1023));
+        assert!(!matches("-1k", 1024));
+        assert!(!matches("-1k", 1025));
+
+        assert!(matches("2k", 1025));
+        assert!(matches("1M", 1));
+
+        // Plain bytes and c compare exactly
+        assert!(matches("1024", 1024));
+        assert!(!matches("1024c", 1023));
+        assert!(matches("-1024c", 1023));
+        assert!(matches("+1024c", 1025));
+    }
+
+    #[test]
+    fn test_parse_size_errors() {
+        for bad in [
+            "",
+            "+",
+            "-",
+            "k",
+            "+k",
+            "xx10Gzz",
+            "10Gzz",
+            "1x",
+            "1.5M",
+            "++1",
+            "99999999999999999999999",
+        ] {
+            assert!(parse_size(bad).is_err(), "{:?} should not parse", bad);
+        }
+        assert_eq!(
+            parse_size("xx10Gzz").unwrap_err(),
+            "illegal size \"xx10Gzz\""
+        );
+    }
+
+    #[test]
+    fn test_format_mode() {
+        assert_eq!(format_mode(0o755), "rwxr-xr-x");
+        assert_eq!(format_mode(0o644), "rw-r--r--");
+        assert_eq!(format_mode(0o000), "---------");
+        assert_eq!(format_mode(0o777), "rwxrwxrwx");
+        assert_eq!(format_mode(0o100600), "rw-------");
+        assert_eq!(format_mode(0o4755), "rwsr-xr-x");
+        assert_eq!(format_mode(0o2644), "rw-r-Sr--");
+        assert_eq!(format_mode(0o1777), "rwxrwxrwt");
+        assert_eq!(format_mode(0o1776), "rwxrwxrwT");
+    }
+
+    #[test]
+    fn test_is_other_device() {
+        assert!(!is_other_device(Some(1), Some(1)));
+        assert!(is_other_device(Some(2), Some(1)));
+
+        // Without a device to compare against nothing is pruned
+        assert!(!is_other_device(None, Some(1)));
+        assert!(!is_other_device(Some(2), None));
+        assert!(!is_other_device(None, None));
+    }
 }
@@ -1,6 +1,9 @@
 fn main() {
-    if let Err(e) = findr::get_args().and_then(findr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match findr::get_args().and_then(findr::run) {
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        Ok(status) => std::process::exit(status.code()),
     }
 }
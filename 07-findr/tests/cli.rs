@@ -1,7 +1,11 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{borrow::Cow, fs, path::Path};
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -22,6 +26,13 @@ fn gen_bad_file() -> String {
     }
 }
 
+// --------------------------------------------------
+fn gen_temp_dir() -> Result<PathBuf, std::io::Error> {
+    let dir = std::env::temp_dir().join(format!("findr-{}", gen_bad_file()));
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
+
 // --------------------------------------------------
 #[test]
 fn skips_bad_dir() -> TestResult {
@@ -49,8 +60,11 @@ fn dies_bad_name() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn dies_bad_type() -> TestResult {
-    let expected =
-        "error: invalid value 'x' for '--type <ENTRY_TYPES>...': [possible values: d, f, l]";
+    let expected = if cfg!(unix) {
+        "error: invalid value 'x' for '--type <ENTRY_TYPES>...': [possible values: b, c, d, f, l, p, s]"
+    } else {
+        "error: invalid value 'x' for '--type <ENTRY_TYPES>...': [possible values: d, f, l]"
+    };
     Command::cargo_bin(PRG)?
         .args(&["--type", "x"])
         .assert()
@@ -77,13 +91,13 @@ fn format_file_name(expected_file: &str) -> Cow<str> {
 fn run(args: &[&str], expected_file: &str) -> TestResult {
     let file = format_file_name(expected_file);
     let contents = fs::read_to_string(file.as_ref())?;
-    let mut expected: Vec<&str> = contents.split("\n").filter(|s| !s.is_empty()).collect();
+    let mut expected: Vec<&str> = contents.lines().collect();
     expected.sort();
 
     let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
     let out = cmd.get_output();
     let stdout = String::from_utf8(out.stdout.clone())?;
-    let mut lines: Vec<&str> = stdout.split("\n").filter(|s| !s.is_empty()).collect();
+    let mut lines: Vec<&str> = stdout.lines().collect();
     lines.sort();
 
     assert_eq!(lines, expected);
@@ -338,3 +352,1045 @@ fn unreadable_dir() -> TestResult {
     assert!(stderr.contains("cant-touch-this: Permission denied"));
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn exec_echo() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs/a",
+            "-t",
+            "f",
+            "--exec",
+            "echo",
+            "hit",
+            "{}",
+            ";",
+        ])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+
+    assert_eq!(
+        lines,
+        [
+            "hit tests/inputs/a/a.txt",
+            "hit tests/inputs/a/b/b.csv",
+            "hit tests/inputs/a/b/c/c.mp3",
+        ]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_with_print() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs/f",
+            "-t",
+            "f",
+            "--print",
+            "--exec",
+            "echo",
+            "{}",
+            ";",
+        ])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+
+    assert_eq!(stdout, "tests/inputs/f/f.txt\n".repeat(2));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exec_batch() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "-t", "f", "--exec", "echo", "{}", "+"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut paths: Vec<&str> = stdout.split_whitespace().collect();
+    paths.sort();
+
+    assert_eq!(stdout.lines().count(), 1);
+    assert_eq!(
+        paths,
+        [
+            "tests/inputs/a/a.txt",
+            "tests/inputs/a/b/b.csv",
+            "tests/inputs/a/b/c/c.mp3",
+        ]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_failure_continues() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "-t", "f", "--exec", "false", ";"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains("false: exit status: 1").count(3));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn print0_round_trips_through_xargs() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::write(dir.join("with space.txt"), "one\n")?;
+    fs::write(dir.join("with\nnewline.txt"), "two\n")?;
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["-t", "f", "-0"])
+        .assert()
+        .success();
+    let stdout = cmd.get_output().stdout.clone();
+    assert_eq!(stdout.iter().filter(|&&b| b == 0).count(), 2);
+    assert!(stdout.ends_with(b"\0"));
+
+    let cat = Command::new("xargs")
+        .args(["-0", "cat"])
+        .write_stdin(stdout)
+        .assert()
+        .success();
+    let mut lines: Vec<String> = String::from_utf8(cat.get_output().stdout.clone())?
+        .lines()
+        .map(String::from)
+        .collect();
+    lines.sort();
+
+    fs::remove_dir_all(&dir)?;
+    assert_eq!(lines, ["one", "two"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_matches() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-n", "no-such-name"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn print0_no_matches() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-0", "-n", "no-such-name"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::write(dir.join("empty.txt"), "")?;
+    fs::write(dir.join("full.txt"), "data\n")?;
+    fs::create_dir(dir.join("empty-dir"))?;
+    fs::create_dir(dir.join("full-dir"))?;
+    fs::write(dir.join("full-dir").join("file.txt"), "data\n")?;
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .arg("--empty")
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+
+    let expected = [dir.join("empty-dir"), dir.join("empty.txt")];
+    let expected: Vec<String> = expected.iter().map(|p| p.display().to_string()).collect();
+
+    fs::remove_dir_all(&dir)?;
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(not(windows))]
+fn touch(path: &Path, date: &str) -> TestResult {
+    std::process::Command::new("touch")
+        .args(["-m", "-d", date])
+        .arg(path)
+        .status()?;
+    Ok(())
+}
+
+// --------------------------------------------------
+fn find_names(dir: &Path, args: &[&str]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(dir)
+        .args(args)
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut names: Vec<String> = stdout
+        .lines()
+        .map(|line| {
+            Path::new(line)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn mtime_mmin_newer() -> TestResult {
+    let dir = gen_temp_dir()?;
+    let old = dir.join("old.txt");
+    let reference = dir.join("reference.txt");
+    let new = dir.join("new.txt");
+    for file in [&old, &reference, &new] {
+        fs::write(file, "")?;
+    }
+    touch(&old, "10 days ago")?;
+    touch(&reference, "5 days ago")?;
+
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--mtime", "+7"])?,
+        ["old.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--mtime", "10"])?,
+        ["old.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--mtime", "-1"])?,
+        ["new.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--mtime", "-7"])?,
+        ["new.txt", "reference.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--mmin", "-60"])?,
+        ["new.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--newer", reference.to_str().unwrap()])?,
+        ["new.txt"]
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_mtime() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--mtime", "1d"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal age \"1d\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn iname() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::write(dir.join("README.md"), "")?;
+    fs::write(dir.join("readme.txt"), "")?;
+    fs::write(dir.join("other.txt"), "")?;
+
+    assert_eq!(find_names(&dir, &["--name", "readme"])?, ["readme.txt"]);
+    assert_eq!(
+        find_names(&dir, &["--iname", "readme"])?,
+        ["README.md", "readme.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["--name", "other", "--iname", "README"])?,
+        ["README.md", "other.txt", "readme.txt"]
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_iname() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--iname", "*.csv"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --iname \"*.csv\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn glob() -> TestResult {
+    let dir = gen_temp_dir()?;
+    for name in [
+        "lib.rs",
+        "main.rs",
+        "a1.txt",
+        "b2.txt",
+        "ab.txt",
+        "file.name",
+        "fileXname",
+    ] {
+        fs::write(dir.join(name), "")?;
+    }
+
+    assert_eq!(find_names(&dir, &["-g", "*.rs"])?, ["lib.rs", "main.rs"]);
+    assert_eq!(
+        find_names(&dir, &["--glob", "??.txt"])?,
+        ["a1.txt", "ab.txt", "b2.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["--glob", "[a-z][0-9].txt"])?,
+        ["a1.txt", "b2.txt"]
+    );
+    assert_eq!(find_names(&dir, &["--glob", "file.name"])?, ["file.name"]);
+    assert_eq!(
+        find_names(&dir, &["--name", "file.name"])?,
+        ["file.name", "fileXname"]
+    );
+    assert_eq!(
+        find_names(&dir, &["--glob", "*.rs", "--name", "^ab"])?,
+        ["ab.txt", "lib.rs", "main.rs"]
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_glob() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--glob", "[a-"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --glob \"[a-\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn path_patterns() -> TestResult {
+    let dir = gen_temp_dir()?;
+    for sub in ["target/debug", "src", "Target/release"] {
+        fs::create_dir_all(dir.join(sub))?;
+    }
+    fs::write(dir.join("target/debug/app"), "")?;
+    fs::write(dir.join("src/main.rs"), "")?;
+    fs::write(dir.join("src/target.rs"), "")?;
+    fs::write(dir.join("Target/release/app"), "")?;
+
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--path", "/target/"])?,
+        ["app"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--ipath", "/target/"])?,
+        ["app", "app"]
+    );
+    assert_eq!(
+        find_names(
+            &dir,
+            &["-t", "f", "--full-path", "/src/", "--path", "/debug/"]
+        )?,
+        ["app", "main.rs", "target.rs"]
+    );
+    assert_eq!(
+        find_names(&dir, &["--path", "/src/", "--name", "^target"])?,
+        ["target.rs"]
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::create_dir(dir.join("sub"))?;
+    for name in ["keep.txt", "a.tmp", "sub/b.tmp", "sub/keep.rs"] {
+        fs::write(dir.join(name), "")?;
+    }
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--glob", "*.tmp", "--delete", "--verbose"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(stdout.contains("a.tmp'"));
+    assert!(stdout.contains("b.tmp'"));
+
+    assert_eq!(find_names(&dir, &["-t", "f"])?, ["keep.rs", "keep.txt"]);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_reports_failures() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::create_dir(dir.join("full"))?;
+    fs::write(dir.join("full/keep.txt"), "")?;
+    fs::create_dir(dir.join("empty"))?;
+
+    Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--min-depth", "1", "-t", "d", "--delete"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains("full: "));
+
+    assert!(!dir.join("empty").exists());
+    assert!(dir.join("full/keep.txt").exists());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delete_without_filter() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::write(dir.join("file.txt"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .arg("--delete")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Refusing to --delete without a filter",
+        ));
+    assert!(dir.join("file.txt").exists());
+
+    Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--delete", "--force"])
+        .assert()
+        .success();
+    assert!(!dir.exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exclude() -> TestResult {
+    let dir = gen_temp_dir()?;
+    for sub in [".git/objects", "node_modules/pkg", "src"] {
+        fs::create_dir_all(dir.join(sub))?;
+    }
+    for i in 0..50 {
+        let sub = dir.join(format!("target/debug/{}", i));
+        fs::create_dir_all(&sub)?;
+        fs::write(sub.join("main.rs"), "")?;
+    }
+    fs::write(dir.join(".git/objects/main.rs"), "")?;
+    fs::write(dir.join("node_modules/pkg/index.js"), "")?;
+    fs::write(dir.join("src/main.rs"), "")?;
+
+    assert_eq!(
+        find_names(
+            &dir,
+            &[
+                "--min-depth",
+                "1",
+                "--exclude",
+                "target",
+                ".git",
+                "node_modules"
+            ]
+        )?,
+        ["main.rs", "src"]
+    );
+    assert_eq!(
+        find_names(
+            &dir,
+            &["--prune", "node_*", "--exclude", "t*", "-n", "main"]
+        )?,
+        ["main.rs", "main.rs"]
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_links() -> TestResult {
+    use std::os::unix::fs::symlink;
+
+    let dir = gen_temp_dir()?;
+    fs::create_dir_all(dir.join("real/sub"))?;
+    fs::write(dir.join("real/file.txt"), "")?;
+    symlink("real", dir.join("linkdir"))?;
+    symlink("real/file.txt", dir.join("linkfile"))?;
+    symlink("nowhere", dir.join("dangling"))?;
+    symlink("../..", dir.join("real/sub/loop"))?;
+
+    // Without -L the links are not descended into
+    assert_eq!(
+        find_names(&dir, &["-t", "l"])?,
+        ["dangling", "linkdir", "linkfile", "loop"]
+    );
+    assert_eq!(find_names(&dir, &["-t", "f"])?, ["file.txt"]);
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["-L", "-t", "f"])
+        .assert()
+        .success();
+    let out = cmd.get_output();
+    let mut lines: Vec<String> = String::from_utf8(out.stdout.clone())?
+        .lines()
+        .map(|line| line.replace(&dir.display().to_string(), ""))
+        .collect();
+    lines.sort();
+    assert_eq!(lines, ["/linkdir/file.txt", "/linkfile", "/real/file.txt"]);
+
+    let stderr = String::from_utf8(out.stderr.clone())?;
+    assert!(stderr.contains("File system loop found"));
+    assert!(!stderr.contains("dangling"));
+
+    // Like find -L, a link that can't be followed is listed as a link
+    for threads in ["1", "2"] {
+        Command::cargo_bin(PRG)?
+            .arg(&dir)
+            .args(["--follow", "-t", "l", "--threads", threads])
+            .assert()
+            .success()
+            .stdout(format!("{}\n", dir.join("dangling").display()));
+    }
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+fn id(flag: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let out = std::process::Command::new("id").arg(flag).output()?;
+    Ok(String::from_utf8(out.stdout)?.trim().to_string())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn user_and_group() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::write(dir.join("mine.txt"), "")?;
+
+    let (uid, gid, user) = (id("-u")?, id("-g")?, id("-un")?);
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--user", &uid])?,
+        ["mine.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--user", &user])?,
+        ["mine.txt"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--user", &uid, "--group", &gid])?,
+        ["mine.txt"]
+    );
+    assert!(find_names(&dir, &["--user", "4000000000"])?.is_empty());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn dies_unknown_user() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--user", "no-such-user-findr"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Unknown user \"no-such-user-findr\"",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn xdev_same_filesystem() -> TestResult {
+    run(&["tests/inputs", "--xdev"], "tests/expected/path1.txt")?;
+    run(
+        &["tests/inputs/a", "--one-file-system"],
+        "tests/expected/path_a.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn not_name_path_type() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::create_dir(dir.join("obj"))?;
+    for name in ["main.c", "main.o", "obj/util.o", "obj/util.c"] {
+        fs::write(dir.join(name), "")?;
+    }
+
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--not-name", r"\.o$"])?,
+        ["main.c", "util.c"]
+    );
+    assert_eq!(
+        find_names(&dir, &["-t", "f", "--not-path", "/obj/"])?,
+        ["main.c", "main.o"]
+    );
+    assert_eq!(
+        find_names(&dir, &["--min-depth", "1", "--not-type", "f"])?,
+        ["obj"]
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn any() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::create_dir(dir.join("sub"))?;
+    fs::write(dir.join("small.txt"), "small")?;
+    fs::write(dir.join("big.bin"), vec![0; 2 * 1024 * 1024])?;
+
+    assert_eq!(
+        find_names(&dir, &["--min-depth", "1", "--type", "d", "--size", "+1M"])?,
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        find_names(
+            &dir,
+            &["--min-depth", "1", "--any", "--type", "d", "--size", "+1M"]
+        )?,
+        ["big.bin", "sub"]
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn ls() -> TestResult {
+    let row = concat!(
+        r"^-rw-r--r--\s+1 \S+\s+\S+\s+5 ",
+        r"\d{4}-\d{2}-\d{2} \d{2}:\d{2} .*/listed\.txt\n$"
+    );
+
+    let dir = gen_temp_dir()?;
+    let file = dir.join("listed.txt");
+    fs::write(&file, "hello")?;
+    std::process::Command::new("chmod")
+        .args(["644"])
+        .arg(&file)
+        .status()?;
+
+    Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["-t", "f", "--ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(row)?);
+
+    Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--max-depth", "0", "--ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^d[rwx-]{9}\s+\d+ ")?);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_size() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--size", "xx10Gzz"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'xx10Gzz' for '--size <SIZE>': illegal size \"xx10Gzz\"",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn size_terabytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--size", "+1T"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+fn stdout_of(args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let cmd = Command::cargo_bin(PRG)?.args(args).assert().success();
+    Ok(String::from_utf8(cmd.get_output().stdout.clone())?)
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_sorted_matches_serial() -> TestResult {
+    for args in [
+        vec!["tests/inputs"],
+        vec!["tests/inputs", "-t", "f", "-n", "[.]csv"],
+        vec!["tests/inputs/a", "tests/inputs/d", "--min-depth", "1"],
+        vec!["tests/inputs", "--max-depth", "2", "--exclude", "b"],
+    ] {
+        let serial = stdout_of(&[args.as_slice(), &["--sort"]].concat())?;
+        let parallel = stdout_of(&[args.as_slice(), &["--threads", "4", "--sort"]].concat())?;
+        assert_eq!(serial, parallel, "{:?}", args);
+    }
+
+    run(
+        &["tests/inputs", "--threads", "4"],
+        "tests/expected/path1.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_threads_with_delete() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "2", "--delete", "-n", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore_and_hidden() -> TestResult {
+    let dir = gen_temp_dir()?;
+    for sub in ["build", "src", ".config"] {
+        fs::create_dir(dir.join(sub))?;
+    }
+    fs::write(dir.join(".gitignore"), "build/\n*.log\n")?;
+    fs::write(dir.join("src/.ignore"), "generated.rs\n")?;
+    for name in [
+        "build/out.o",
+        "app.log",
+        "src/main.rs",
+        "src/generated.rs",
+        ".config/rc",
+    ] {
+        fs::write(dir.join(name), "")?;
+    }
+
+    let all = [
+        ".config",
+        ".gitignore",
+        ".ignore",
+        "app.log",
+        "build",
+        "generated.rs",
+        "main.rs",
+        "out.o",
+        "rc",
+        "src",
+    ];
+    assert_eq!(find_names(&dir, &["--min-depth", "1"])?, all);
+
+    for threads in ["1", "4"] {
+        assert_eq!(
+            find_names(
+                &dir,
+                &["--min-depth", "1", "--gitignore", "--threads", threads]
+            )?,
+            ["main.rs", "src"]
+        );
+        assert_eq!(
+            find_names(
+                &dir,
+                &[
+                    "--min-depth",
+                    "1",
+                    "--gitignore",
+                    "--hidden",
+                    "--threads",
+                    threads
+                ]
+            )?,
+            [".config", ".gitignore", ".ignore", "main.rs", "rc", "src"]
+        );
+    }
+
+    // With --depth a directory still comes after its contents
+    Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--min-depth", "1", "--gitignore", "--depth", "--sort"])
+        .assert()
+        .success()
+        .stdout(format!("{0}/src/main.rs\n{0}/src\n", dir.display()));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn special_types() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::write(dir.join("file"), "")?;
+    let status = std::process::Command::new("mkfifo")
+        .arg(dir.join("pipe"))
+        .status()?;
+    assert!(status.success());
+    let _socket = std::os::unix::net::UnixListener::bind(dir.join("sock"))?;
+
+    assert_eq!(find_names(&dir, &["--type", "p"])?, ["pipe"]);
+    assert_eq!(find_names(&dir, &["--type", "s"])?, ["sock"]);
+    assert_eq!(find_names(&dir, &["--type", "f"])?, ["file"]);
+    assert_eq!(find_names(&dir, &["--type", "p", "s"])?, ["pipe", "sock"]);
+    assert!(find_names(&dir, &["--type", "b", "c"])?.is_empty());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn printf() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::write(dir.join("five"), "12345")?;
+    fs::create_dir(dir.join("sub"))?;
+    fs::write(dir.join("sub/empty"), "")?;
+
+    let output = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--sort", "--min-depth", "1", "--printf", "%f\\t%s\\t%d\\n"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines[0], "five\t5\t1");
+    assert_eq!(lines[2], "empty\t0\t2");
+    assert_eq!(lines.len(), 3);
+
+    let output = Command::cargo_bin(PRG)?
+        .arg(dir.join("five"))
+        .args(["--printf", "[%p]"])
+        .output()?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("[{}]", dir.join("five").display())
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_printf() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--printf", "%p %q"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown directive \"%q\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn max_results() -> TestResult {
+    let dir = gen_temp_dir()?;
+    let mut deep = dir.join("a");
+    for _ in 0..50 {
+        deep = deep.join("d");
+    }
+    fs::create_dir_all(&deep)?;
+    // Only reported when the walk gets this far
+    std::os::unix::fs::symlink(&dir, dir.join("z-loop"))?;
+
+    for threads in ["1", "4"] {
+        let output = Command::cargo_bin(PRG)?
+            .arg(&dir)
+            .args(["-L", "--sort", "--max-results", "2", "--threads", threads])
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout)?.lines().count(), 2);
+    }
+
+    // The walk stops before reaching the loop or the missing second root
+    Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .arg(dir.join("missing"))
+        .args(["-L", "--sort", "--max-results", "2"])
+        .assert()
+        .success()
+        .stderr("");
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quit_on_first() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--sort", "--name", "csv", "--quit-on-first"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/a/b/b.csv\n");
+
+    Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs",
+            "--name",
+            "nothing-like-this",
+            "--quit-on-first",
+        ])
+        .assert()
+        .failure()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn depth_first() -> TestResult {
+    let dir = gen_temp_dir()?;
+    fs::create_dir_all(dir.join("a/b"))?;
+    fs::write(dir.join("a/b/c.txt"), "")?;
+    fs::write(dir.join("a/d.txt"), "")?;
+
+    let relative = |stdout: String| -> Vec<String> {
+        stdout
+            .lines()
+            .map(|line| {
+                line.strip_prefix(&*dir.to_string_lossy())
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    };
+
+    let output = Command::cargo_bin(PRG)?.arg(&dir).arg("--sort").output()?;
+    assert_eq!(
+        relative(String::from_utf8(output.stdout)?),
+        ["", "/a", "/a/b", "/a/b/c.txt", "/a/d.txt"]
+    );
+
+    for flag in ["-d", "--depth"] {
+        let output = Command::cargo_bin(PRG)?
+            .arg(&dir)
+            .args(["--sort", flag])
+            .output()?;
+        assert_eq!(
+            relative(String::from_utf8(output.stdout)?),
+            ["/a/b/c.txt", "/a/b", "/a/d.txt", "/a", ""]
+        );
+    }
+
+    let output = Command::cargo_bin(PRG)?
+        .arg(&dir)
+        .args(["--sort", "--depth", "--min-depth", "0", "--max-depth", "1"])
+        .output()?;
+    assert_eq!(relative(String::from_utf8(output.stdout)?), ["/a", ""]);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_threads_with_depth() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "2", "--depth"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs",
+            "--type",
+            "f",
+            "--name",
+            r"\.txt$",
+            "--count",
+        ])
+        .assert()
+        .success()
+        .stdout("3\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "--name", "nothing-like-this", "--count"])
+        .assert()
+        .success()
+        .stdout("0\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stats() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs",
+            "--type",
+            "f",
+            "--name",
+            r"\.txt$",
+            "--stats",
+        ])
+        .assert()
+        .success()
+        .stdout("Matches: 3\nTotal size: 6\nDirectories: 0\nFiles: 3\nLinks: 0\nMax depth: 2\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/d", "--stats"])
+        .assert()
+        .success()
+        .stdout("Matches: 6\nTotal size: 6\nDirectories: 2\nFiles: 3\nLinks: 1\nMax depth: 2\n");
+    Ok(())
+}
@@ -1,23 +1,86 @@
 use std::{
+    borrow::Cow,
     error::Error,
-    fs::File,
-    io::{self, BufRead, BufReader, Write},
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
     num::NonZeroUsize,
     ops::Range,
 };
 
-use clap::{arg, ArgGroup, Parser};
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use clap::{ArgGroup, Parser, ValueEnum};
+use csv::{ReaderBuilder, StringRecord, Terminator, WriterBuilder};
+use flate2::read::MultiGzDecoder;
+use memchr::memchr_iter;
 use regex::Regex;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 type PositionList = Vec<Range<usize>>;
 
+// A position counted from the start, or with ~N from the end of the record,
+// where ~1 is the last one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Index {
+    Start(usize),
+    End(usize),
+}
+
+// One entry of a position list, such as 3, 1-2 or ~2-~1, both ends included
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    first: Index,
+    last: Index,
+}
+
+type SpanList = Vec<Span>;
+
+// The outcome of a run: a file that could not be opened or read is a
+// failure, after its error has been printed and the other files cut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+}
+
+impl Status {
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Success => 0,
+            Status::Failure => 1,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Extract {
-    Fields(PositionList),
-    Bytes(PositionList),
-    Chars(PositionList),
+    Fields(SpanList),
+    Bytes(SpanList),
+    Chars(SpanList),
+    Names(Vec<String>),
+}
+
+// When the csv writer quotes output fields
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum QuoteStyle {
+    Never,
+    Always,
+    Necessary,
+}
+
+impl From<QuoteStyle> for csv::QuoteStyle {
+    fn from(style: QuoteStyle) -> Self {
+        match style {
+            QuoteStyle::Never => csv::QuoteStyle::Never,
+            QuoteStyle::Always => csv::QuoteStyle::Always,
+            QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+        }
+    }
+}
+
+// What -b does when a selection splits a multibyte character
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Utf8Check {
+    Error,
+    Warn,
 }
 
 #[derive(Parser, Debug)]
@@ -29,7 +92,7 @@ pub enum Extract {
         ArgGroup::new("flags")
             .required(true)
             .multiple(false)
-            .args(["chars", "bytes", "fields"])
+            .args(["chars", "bytes", "fields", "names"])
         ))]
 pub struct Args {
     #[arg(help = "Input File(s) [default: -]", default_value = "-")]
@@ -42,53 +105,227 @@ pub struct Args {
           value_parser = parse_delimiter)]
     delimiter: u8,
 
-    #[arg(help = "Selected bytes", short = 'b', value_parser = parse_bytes, required = false)]
+    #[arg(
+        help = "Split fields on runs of whitespace, ignoring leading whitespace",
+        short = 'w',
+        long,
+        conflicts_with = "delimiter"
+    )]
+    whitespace: bool,
+
+    #[arg(
+        help = "Split fields on matches of PATTERN, ignoring csv quoting",
+        long,
+        value_name = "PATTERN",
+        value_parser = parse_regex_delimiter,
+        conflicts_with_all = ["delimiter", "whitespace"]
+    )]
+    regex_delimiter: Option<Regex>,
+
+    #[arg(help = "Selected bytes, ~N counts from the end", short = 'b', value_parser = parse_bytes, required = false)]
     bytes: Option<Extract>,
 
-    #[arg(help = "Selected Characters", short = 'c', value_parser = parse_chars, required = false)]
+    #[arg(
+        help = "With -b, fail when a selection splits a multibyte character in UTF-8 text, or only warn with =warn",
+        long,
+        value_name = "MODE",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "error",
+        conflicts_with_all = ["chars", "fields", "names"]
+    )]
+    strict_utf8: Option<Utf8Check>,
+
+    #[arg(help = "Selected Characters, ~N counts from the end", short = 'c', value_parser = parse_chars, required = false)]
     chars: Option<Extract>,
 
-    #[arg(help = "Selected fields", short = 'f', value_parser = parse_fields, required = false)]
+    #[arg(help = "Selected fields, ~N counts from the end (~1 is the last)", short = 'f', value_parser = parse_fields, required = false)]
     fields: Option<Extract>,
 
-    #[arg(help = "Output field delimiter (defaults to input delimiter)", long, value_parser = parse_delimiter)]
-    output_delimiter: Option<u8>,
+    #[arg(
+        help = "Selected fields by name, looked up in the first record of each file",
+        long,
+        value_parser = parse_names,
+        conflicts_with_all = ["whitespace", "regex_delimiter"]
+    )]
+    names: Option<Extract>,
+
+    #[arg(help = "Leave out the header with --names", long, requires = "names")]
+    no_header_out: bool,
+
+    #[arg(
+        help = "Cut each file's first record like any other [default]",
+        long,
+        conflicts_with = "header_only_first"
+    )]
+    header: bool,
+
+    #[arg(
+        help = "Write the first record only for the first file, so concatenated output has one header",
+        long,
+        conflicts_with_all = ["bytes", "chars"]
+    )]
+    header_only_first: bool,
+
+    #[arg(
+        help = "Only cut records START to END, counted from 1 in each file after any header (either end may be left out)",
+        long,
+        value_name = "START:END",
+        value_parser = parse_line_range
+    )]
+    lines: Option<Range<usize>>,
+
+    #[arg(
+        help = "Flush the output after every record, for use in streaming pipelines",
+        long
+    )]
+    line_buffered: bool,
+
+    #[arg(help = "Records end with NUL instead of newline", short = 'z', long)]
+    zero_terminated: bool,
+
+    #[arg(
+        help = "Output field delimiter, any string (defaults to input delimiter, a space with -w or a tab with --regex-delimiter); also put between selected -b/-c ranges",
+        long
+    )]
+    output_delimiter: Option<String>,
+
+    #[arg(
+        help = "When to quote output fields, with a single byte output delimiter [default: necessary, never with --keep-quotes]",
+        long,
+        value_enum
+    )]
+    quote_style: Option<QuoteStyle>,
+
+    #[arg(
+        help = "Split fields on the delimiter byte alone and write them as they are, without any csv quoting (faster for plain TSV)",
+        long,
+        conflicts_with_all = ["whitespace", "regex_delimiter", "names", "quote_style", "keep_quotes"]
+    )]
+    no_quote: bool,
+
+    #[arg(
+        help = "Treat quotes in the input as ordinary characters and pass them through",
+        long,
+        conflicts_with_all = ["whitespace", "regex_delimiter"]
+    )]
+    keep_quotes: bool,
 
     #[arg(help = "Output file (defaults to STDOUT)", short = 'o', long)]
     output_file: Option<String>,
+
+    #[arg(
+        help = "Append to the output file instead of truncating it",
+        long,
+        requires = "output_file"
+    )]
+    append: bool,
+
+    #[arg(
+        help = "Decompress input with gzip (detected automatically from the content or a .gz name)",
+        long
+    )]
+    gzip: bool,
+
+    #[arg(help = "Select everything except the given positions", long)]
+    complement: bool,
+
+    #[arg(
+        help = "Skip lines without the delimiter when selecting fields",
+        short = 's',
+        long
+    )]
+    only_delimited: bool,
+
+    #[arg(
+        help = "Output positions in the order given, repeats included [default: like cut, in ascending order, each once]",
+        long
+    )]
+    no_reorder: bool,
 }
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
     delimiter: u8,
+    whitespace: bool,
+    regex_delimiter: Option<Regex>,
     extract: Extract,
-    output_delimiter: u8,
+    output_delimiter: String,
+    range_delimiter: Option<String>,
+    quote_style: QuoteStyle,
+    keep_quotes: bool,
+    no_quote: bool,
     output_file: Option<String>,
+    append: bool,
+    gzip: bool,
+    complement: bool,
+    no_reorder: bool,
+    only_delimited: bool,
+    no_header_out: bool,
+    header_only_first: bool,
+    lines: Range<usize>,
+    line_buffered: bool,
+    strict_utf8: Option<Utf8Check>,
+    terminator: u8,
 }
 
 fn parse_delimiter(input: &str) -> Result<u8, String> {
     let delim_bytes = input.as_bytes();
     if delim_bytes.len() != 1 {
-        return Err(From::from(format!(
-            "--delim \"{}\" must be a single byte",
-            input
-        )));
+        return Err(format!("--delim \"{}\" must be a single byte", input));
     }
     Ok(*delim_bytes.first().unwrap())
 }
 
-fn parse_bytes(input: &str) -> Result<Extract, String> {
-    let pos_list = parse_pos(input).unwrap();
+fn parse_regex_delimiter(input: &str) -> Result<Regex, String> {
+    Regex::new(input).map_err(|_| format!("--regex-delimiter \"{}\" is not a valid regex", input))
+}
 
-    Ok(Extract::Bytes(pos_list))
+fn parse_bytes(input: &str) -> Result<Extract, String> {
+    parse_pos(input).map(Extract::Bytes)
 }
 
 fn parse_chars(input: &str) -> Result<Extract, String> {
-    Ok(Extract::Chars(parse_pos(input).unwrap()))
+    parse_pos(input).map(Extract::Chars)
 }
 
 fn parse_fields(input: &str) -> Result<Extract, String> {
-    Ok(Extract::Fields(parse_pos(input).unwrap()))
+    parse_pos(input).map(Extract::Fields)
+}
+
+fn parse_names(input: &str) -> Result<Extract, String> {
+    let names: Vec<_> = input.split(',').map(String::from).collect();
+    if names.iter().any(|name| name.is_empty()) {
+        return Err(format!("illegal list of names: {}", input));
+    }
+
+    Ok(Extract::Names(names))
+}
+
+// Parse a 1-based inclusive START:END into a range of record indexes
+pub fn parse_line_range(input: &str) -> Result<Range<usize>, String> {
+    let value_error = || format!("illegal line range: {}", input);
+    let parse_end = |end: &str, open| match end {
+        "" => Ok(open),
+        _ if !end.bytes().all(|b| b.is_ascii_digit()) => Err(value_error()),
+        _ => end
+            .parse::<NonZeroUsize>()
+            .map(usize::from)
+            .map_err(|_| value_error()),
+    };
+
+    let (start, end) = input.split_once(':').ok_or_else(value_error)?;
+    let start = parse_end(start, 1)?;
+    let end = parse_end(end, usize::MAX)?;
+    if start > end {
+        return Err(format!(
+            "First line ({}) must not be after the last line ({})",
+            start, end
+        ));
+    }
+
+    Ok(start - 1..end)
 }
 
 // Parse an index from a string representation of an integer.
@@ -101,119 +338,650 @@ fn parse_index(input: &str) -> Result<usize, String> {
 
     let re = Regex::new(r"^\d*$").unwrap();
 
-    (!re.is_match(input))
-        .then(|| Err(value_error()))
-        .unwrap_or_else(|| {
-            input
-                .parse::<NonZeroUsize>()
-                .map(|n| usize::from(n) - 1)
-                .map_err(|_| value_error())
-        })
+    if !re.is_match(input) {
+        Err(value_error())
+    } else {
+        input
+            .parse::<NonZeroUsize>()
+            .map(|n| usize::from(n) - 1)
+            .map_err(|_| value_error())
+    }
+}
+
+// Parse a position, either an index or ~N counting from the end
+fn parse_position(input: &str) -> Result<Index, String> {
+    match input.strip_prefix('~') {
+        Some(n) => parse_index(n)
+            .map(|n| Index::End(n + 1))
+            .map_err(|_| format!("illegal list value: {}", input)),
+        None => parse_index(input).map(Index::Start),
+    }
 }
 
-fn parse_pos(range: &str) -> Result<PositionList, String> {
-    let range_re = Regex::new(r"^(\d+)-(\d+)$").unwrap();
+pub fn parse_pos(range: &str) -> Result<SpanList, String> {
+    let range_re = Regex::new(r"^(~?\d+)-(~?\d+)$").unwrap();
 
     range
         .split(',')
-        .into_iter()
         .map(|val| {
-            parse_index(val).map(|n| n..n + 1).or_else(|e| {
-                range_re.captures(val).ok_or(e).and_then(|captures| {
-                    let n1 = parse_index(&captures[1])?;
-                    let n2 = parse_index(&captures[2])?;
-                    if n1 >= n2 {
-                        return Err(format!(
-                            "First number in range ({}) \
+            parse_position(val)
+                .map(|n| Span { first: n, last: n })
+                .or_else(|e| {
+                    range_re.captures(val).ok_or(e).and_then(|captures| {
+                        let first = parse_position(&captures[1])?;
+                        let last = parse_position(&captures[2])?;
+                        // Mixed ranges like 2-~1 can only be checked per record
+                        let backwards = match (first, last) {
+                            (Index::Start(n1), Index::Start(n2)) => n1 >= n2,
+                            (Index::End(n1), Index::End(n2)) => n1 <= n2,
+                            _ => false,
+                        };
+                        if backwards {
+                            return Err(format!(
+                                "First number in range ({}) \
                                         must be lower than the second number ({})",
-                            n1 + 1,
-                            n2 + 1
-                        ));
-                    }
+                                &captures[1], &captures[2]
+                            ));
+                        }
 
-                    Ok(n1..n2 + 1)
+                        Ok(Span { first, last })
+                    })
                 })
-            })
         })
         .collect::<Result<_, _>>()
-        .map_err(From::from)
+}
+
+// Turns spans into positions in a record of `len` fields, chars or bytes.
+// Single positions past the start of the record select nothing, while a
+// range starting there is cut short.
+pub fn resolve(spans: &[Span], len: usize) -> PositionList {
+    spans
+        .iter()
+        .filter_map(|span| {
+            let first = match span.first {
+                Index::Start(n) => n,
+                Index::End(n) if span.first != span.last => len.saturating_sub(n),
+                Index::End(n) => len.checked_sub(n)?,
+            };
+            let last = match span.last {
+                Index::Start(n) => n,
+                Index::End(n) => len.checked_sub(n)?,
+            };
+            (first <= last).then(|| first..last + 1)
+        })
+        .collect()
+}
+
+fn counts_from_end(span: &Span) -> bool {
+    matches!(span.first, Index::End(_)) || matches!(span.last, Index::End(_))
+}
+
+// Sorts the positions and merges overlapping ranges, so each position is
+// selected once and in ascending order. Adjacent ranges stay apart, like in
+// cut, so --output-delimiter still separates them.
+pub fn normalize_positions(mut positions: PositionList) -> PositionList {
+    positions.sort_by_key(|range| range.start);
+
+    let mut merged: PositionList = vec![];
+    for range in positions {
+        match merged.last_mut() {
+            Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+impl Config {
+    // The positions to cut are worked out once, unless positions from the
+    // end or --complement make them depend on the length of each record
+    fn positions<'a>(&'a self, spans: &'a [Span]) -> Positions<'a> {
+        let fixed = (!self.complement && !spans.iter().any(counts_from_end))
+            .then(|| self.resolve_positions(spans, 0));
+        Positions {
+            config: self,
+            spans,
+            fixed,
+        }
+    }
+
+    fn resolve_positions(&self, spans: &[Span], len: usize) -> PositionList {
+        let mut positions = resolve(spans, len);
+        if !self.no_reorder {
+            positions = normalize_positions(positions);
+        }
+        if self.complement {
+            positions = complement(&positions, len);
+        }
+        positions
+    }
+}
+
+struct Positions<'a> {
+    config: &'a Config,
+    spans: &'a [Span],
+    fixed: Option<PositionList>,
+}
+
+impl Positions<'_> {
+    // The positions for one record, whose length is only asked for when needed
+    fn get(&self, len: impl FnOnce() -> usize) -> Cow<'_, PositionList> {
+        match &self.fixed {
+            Some(positions) => Cow::Borrowed(positions),
+            None => Cow::Owned(self.config.resolve_positions(self.spans, len())),
+        }
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
-    let args = Args::parse();
+    // A bad argument exits 1 like every other cutr error, not clap's 2;
+    // --help and --version still print and exit successfully
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => return Err(e.to_string().trim_end().into()),
+    };
+    // A second read of stdin would silently find nothing
+    if args
+        .files
+        .iter()
+        .filter(|filename| *filename == "-")
+        .count()
+        > 1
+    {
+        return Err("cutr: standard input specified more than once".into());
+    }
+
+    let extract = args
+        .bytes
+        .or(args.chars)
+        .or(args.fields)
+        .or(args.names)
+        .unwrap();
 
     Ok(Config {
         files: args.files,
         delimiter: args.delimiter,
-        extract: args.bytes.or(args.chars).or(args.fields).unwrap(),
-        output_delimiter: args.output_delimiter.unwrap_or(args.delimiter),
+        whitespace: args.whitespace,
+        regex_delimiter: args.regex_delimiter,
+        extract,
+        output_delimiter: args.output_delimiter.clone().unwrap_or_else(|| {
+            if args.whitespace {
+                " ".to_string()
+            } else {
+                char::from(args.delimiter).to_string()
+            }
+        }),
+        range_delimiter: args.output_delimiter,
+        quote_style: args.quote_style.unwrap_or(if args.keep_quotes {
+            QuoteStyle::Never
+        } else {
+            QuoteStyle::Necessary
+        }),
+        keep_quotes: args.keep_quotes,
+        no_quote: args.no_quote,
         output_file: args.output_file,
+        append: args.append,
+        gzip: args.gzip,
+        complement: args.complement,
+        no_reorder: args.no_reorder,
+        only_delimited: args.only_delimited,
+        no_header_out: args.no_header_out,
+        header_only_first: args.header_only_first,
+        lines: args.lines.unwrap_or(0..usize::MAX),
+        line_buffered: args.line_buffered,
+        strict_utf8: args.strict_utf8,
+        terminator: if args.zero_terminated { b'\0' } else { b'\n' },
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let mut out_file: Box<dyn Write> = match config.output_file {
-        Some(output_file_name) => Box::new(File::create(output_file_name)?),
+// Opening the output would truncate an input before it is read
+fn check_output_not_input(output_file: &str, files: &[String]) -> MyResult<()> {
+    // An output file that does not exist yet cannot be an input
+    let Ok(output) = fs::canonicalize(output_file) else {
+        return Ok(());
+    };
+
+    let clobbered = files
+        .iter()
+        .filter(|filename| *filename != "-")
+        .any(|filename| fs::canonicalize(filename).is_ok_and(|input| input == output));
+    if clobbered {
+        return Err(format!("output file \"{}\" is also an input file", output_file).into());
+    }
+
+    Ok(())
+}
+
+pub fn run(config: Config) -> MyResult<Status> {
+    let mut out_file: Box<dyn Write> = match &config.output_file {
+        Some(output_file_name) => {
+            check_output_not_input(output_file_name, &config.files)?;
+            if config.append {
+                Box::new(
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(output_file_name)?,
+                )
+            } else {
+                Box::new(File::create(output_file_name)?)
+            }
+        }
         _ => Box::new(io::stdout()),
     };
 
+    let mut header_done = false;
+    let mut failed = false;
     for filename in &config.files {
-        match open(filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(file) => match &config.extract {
-                Extract::Bytes(bytes_pos) => {
-                    for line in file.lines() {
-                        writeln!(&mut out_file, "{}", extract_bytes(&line?, bytes_pos))?
+        let result = match open(filename, config.gzip || filename.ends_with(".gz")) {
+            Err(err) => Err(err),
+            Ok(file) => cut(file, filename, &config, &mut out_file, &mut header_done),
+        };
+        match result {
+            // Unreadable files, or read errors such as a corrupt gzip
+            // stream, only end this file
+            Err(err) if err.is::<io::Error>() || err.is::<csv::Error>() => {
+                eprintln!("cutr: {}: {}", filename, err);
+                failed = true;
+            }
+            result => result?,
+        }
+    }
+
+    out_file.flush()?;
+    if failed {
+        Ok(Status::Failure)
+    } else {
+        Ok(Status::Success)
+    }
+}
+
+fn cut(
+    file: Box<dyn BufRead>,
+    filename: &str,
+    config: &Config,
+    out_file: &mut dyn Write,
+    header_done: &mut bool,
+) -> MyResult<()> {
+    match &config.extract {
+        Extract::Bytes(bytes_pos) => {
+            // Records stay raw bytes, so binary or non-UTF-8 input
+            // is cut and written unchanged
+            let records = file.split(config.terminator).enumerate();
+            let positions = config.positions(bytes_pos);
+            for (i, record) in within_lines(records, &config.lines) {
+                let record = record?;
+                let positions = positions.get(|| record.len());
+                if let Some(check) = config.strict_utf8 {
+                    if let Some(offset) = split_char_offset(&record, &positions) {
+                        let message = format!(
+                            "line {}: selection splits a multibyte character at byte {}",
+                            i + 1,
+                            offset + 1
+                        );
+                        match check {
+                            // Reported like a read error, so the next file is still cut
+                            Utf8Check::Error => {
+                                return Err(
+                                    io::Error::new(io::ErrorKind::InvalidData, message).into()
+                                )
+                            }
+                            Utf8Check::Warn => {
+                                eprintln!("cutr: warning: {}: {}", filename, message)
+                            }
+                        }
                     }
                 }
-                Extract::Chars(char_pos) => {
-                    for line in file.lines() {
-                        writeln!(&mut out_file, "{}", extract_chars(&line?, char_pos))?
+                let selected = match &config.range_delimiter {
+                    Some(delim) => {
+                        join_ranges(&positions, delim, |range| extract_raw_bytes(&record, range))
                     }
+                    None => extract_raw_bytes(&record, &positions),
+                };
+                out_file.write_all(&selected)?;
+                end_record(out_file, config)?;
+            }
+        }
+        Extract::Chars(char_pos) => {
+            let positions = config.positions(char_pos);
+            for line in within_lines(read_records(file, config.terminator), &config.lines) {
+                let line = line?;
+                let positions = positions.get(|| line.chars().count());
+                let selected = match &config.range_delimiter {
+                    Some(delim) => {
+                        join_ranges(&positions, delim, |range| extract_chars(&line, range))
+                    }
+                    None => extract_chars(&line, &positions).into_bytes(),
+                };
+                out_file.write_all(&selected)?;
+                end_record(out_file, config)?;
+            }
+        }
+        Extract::Fields(field_pos) if config.no_quote => {
+            let positions = config.positions(field_pos);
+            let records = file.split(config.terminator);
+            for record in after_header(records, config, header_done) {
+                let mut record = record?;
+                // Like the csv reader, take \r\n as a line ending
+                if config.terminator == b'\n' && record.ends_with(b"\r") {
+                    record.pop();
+                }
+                let fields = split_raw_fields(&record, config.delimiter);
+                if config.only_delimited && fields.len() == 1 {
+                    continue;
                 }
-                Extract::Fields(field_pos) => {
-                    let mut reader = ReaderBuilder::new()
-                        .delimiter(config.delimiter)
-                        .has_headers(false)
-                        .from_reader(file);
-
-                    let mut writer = WriterBuilder::new()
-                        .delimiter(config.output_delimiter)
-                        .from_writer(&mut out_file);
-
-                    for record in reader.records() {
-                        let record = record?;
-                        writer.write_record(extract_fields(&record, field_pos))?;
+
+                let positions = positions.get(|| fields.len());
+                let selected = extract_split_fields(&fields, &positions);
+                out_file.write_all(&selected.join(config.output_delimiter.as_bytes()))?;
+                end_record(out_file, config)?;
+            }
+        }
+        Extract::Fields(field_pos) => {
+            let positions = config.positions(field_pos);
+            let mut writer = FieldWriter::new(out_file, config);
+
+            if config.whitespace || config.regex_delimiter.is_some() {
+                // The csv reader is bypassed and lines are split by
+                // hand, so quotes have no special meaning
+                let records = read_records(file, config.terminator);
+                for line in after_header(records, config, header_done) {
+                    let line = line?;
+                    let fields: Vec<_> = match &config.regex_delimiter {
+                        Some(re) => re.split(&line).collect(),
+                        None => line.split_whitespace().collect(),
+                    };
+                    if config.only_delimited && fields.len() == 1 {
+                        continue;
                     }
+
+                    let positions = positions.get(|| fields.len());
+                    writer.write(&extract_split_fields(&fields, &positions), config)?;
+                }
+            } else {
+                let mut reader = csv_reader(file, config);
+
+                for record in after_header(reader.records(), config, header_done) {
+                    let record = record?;
+                    // A line without the delimiter reads as a single field
+                    if config.only_delimited && record.len() == 1 {
+                        continue;
+                    }
+
+                    let positions = positions.get(|| record.len());
+                    writer.write(&extract_fields(&record, &positions), config)?;
                 }
-            },
+            }
+        }
+        Extract::Names(names) => {
+            let mut writer = FieldWriter::new(out_file, config);
+            let mut reader = csv_reader(file, config);
+            let mut records = reader.records();
+
+            let header = match records.next() {
+                Some(header) => header?,
+                None => return Ok(()),
+            };
+            let mut field_pos =
+                resolve_names(&header, names).map_err(|e| format!("{}: {}", filename, e))?;
+            if config.complement {
+                field_pos = complement(&field_pos, header.len());
+            }
+
+            let skip = skip_header(config, header_done);
+            if !config.no_header_out && !skip {
+                writer.write(&extract_fields(&header, &field_pos), config)?;
+            }
+            for record in within_lines(records, &config.lines) {
+                writer.write(&extract_fields(&record?, &field_pos), config)?;
+            }
         }
     }
 
     Ok(())
 }
 
-pub fn extract_chars(line: &str, char_pos: &[Range<usize>]) -> String {
-    let chars: Vec<_> = line.chars().collect();
+// Keeps the records within --lines, reading no further than its end
+fn within_lines<I: Iterator>(records: I, lines: &Range<usize>) -> impl Iterator<Item = I::Item> {
+    records.skip(lines.start).take(lines.len())
+}
+
+// With --header-only-first each file's first record is its header, which
+// is kept ahead of the --lines range (or dropped by skip_header) so that
+// only the records after it are numbered
+fn after_header<I: Iterator>(
+    mut records: I,
+    config: &Config,
+    header_done: &mut bool,
+) -> impl Iterator<Item = I::Item> {
+    let header = if config.header_only_first {
+        records.next().filter(|_| !skip_header(config, header_done))
+    } else {
+        None
+    };
+    header
+        .into_iter()
+        .chain(within_lines(records, &config.lines))
+}
+
+// Called with each file's first record. It is skipped when only the first
+// file's header is wanted and an earlier file already had one.
+fn skip_header(config: &Config, header_done: &mut bool) -> bool {
+    let skip = config.header_only_first && *header_done;
+    *header_done = true;
+    skip
+}
+
+// Looks up each name in the header, failing on names that are missing or
+// that more than one column has
+pub fn resolve_names(header: &StringRecord, names: &[String]) -> Result<PositionList, String> {
+    let mut positions = vec![];
+    let mut unknown = vec![];
+
+    for name in names {
+        let mut matches = header.iter().enumerate().filter(|(_, field)| field == name);
+        match (matches.next(), matches.next()) {
+            (Some((i, _)), None) => positions.push(i..i + 1),
+            (Some(_), Some(_)) => {
+                return Err(format!(
+                    "field name \"{}\" appears more than once in the header",
+                    name
+                ))
+            }
+            (None, _) => unknown.push(format!("\"{}\"", name)),
+        }
+    }
+
+    if !unknown.is_empty() {
+        return Err(format!(
+            "unknown field name(s) {}, available: {}",
+            unknown.join(", "),
+            header.iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+
+    Ok(positions)
+}
+
+// Writes selected fields with the csv writer when the output delimiter is a
+// single byte, and otherwise joins them by hand without any quoting
+// The csv writer is only taken out while a record is written
+enum FieldWriter<W: Write> {
+    Csv(Option<Box<csv::Writer<W>>>),
+    Joined(W, String, u8),
+}
+
+impl<W: Write> FieldWriter<W> {
+    fn new(out: W, config: &Config) -> Self {
+        match config.output_delimiter.as_bytes() {
+            [_] => FieldWriter::Csv(Some(csv_writer(out, config))),
+            _ => FieldWriter::Joined(out, config.output_delimiter.clone(), config.terminator),
+        }
+    }
+
+    // Writes one record, passing it straight on to the output with --line-buffered
+    fn write(&mut self, fields: &[&str], config: &Config) -> MyResult<()> {
+        match self {
+            FieldWriter::Csv(slot) => {
+                let mut writer = slot.take().ok_or("output failed")?;
+                if let [] | [""] = fields {
+                    // The csv writer quotes a record with no data as "",
+                    // where cut prints an empty line
+                    let mut out = writer.into_inner().map_err(|e| e.into_error())?;
+                    out.write_all(&[config.terminator])?;
+                    writer = csv_writer(out, config);
+                } else {
+                    writer.write_record(fields)?;
+                }
+                if config.line_buffered {
+                    writer.flush()?;
+                }
+                *slot = Some(writer);
+            }
+            FieldWriter::Joined(out, delimiter, terminator) => {
+                write!(out, "{}{}", fields.join(delimiter), char::from(*terminator))?;
+                if config.line_buffered {
+                    out.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
-    char_pos
+fn csv_writer<W: Write>(out: W, config: &Config) -> Box<csv::Writer<W>> {
+    Box::new(
+        WriterBuilder::new()
+            .delimiter(config.output_delimiter.as_bytes()[0])
+            .terminator(Terminator::Any(config.terminator))
+            .quote_style(config.quote_style.into())
+            .flexible(true)
+            .from_writer(out),
+    )
+}
+
+// Ends a record written by hand, flushing it at once with --line-buffered
+fn end_record(out_file: &mut dyn Write, config: &Config) -> io::Result<()> {
+    out_file.write_all(&[config.terminator])?;
+    if config.line_buffered {
+        out_file.flush()?;
+    }
+    Ok(())
+}
+
+fn csv_reader(file: Box<dyn BufRead>, config: &Config) -> csv::Reader<Box<dyn BufRead>> {
+    ReaderBuilder::new()
+        .delimiter(config.delimiter)
+        .terminator(csv_terminator(config.terminator))
+        .quoting(!config.keep_quotes)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(file)
+}
+
+// For newlines keep the reader's default, which also accepts \r\n
+fn csv_terminator(terminator: u8) -> Terminator {
+    match terminator {
+        b'\n' => Terminator::CRLF,
+        _ => Terminator::Any(terminator),
+    }
+}
+
+// Like lines(), but records may end in NUL rather than newline
+fn read_records(
+    file: Box<dyn BufRead>,
+    terminator: u8,
+) -> Box<dyn Iterator<Item = io::Result<String>>> {
+    match terminator {
+        b'\n' => Box::new(file.lines()),
+        _ => Box::new(file.split(terminator).map(|record| {
+            String::from_utf8(record?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })),
+    }
+}
+
+// Extracts each range on its own and joins the non-empty pieces, as cut does
+// when given --output-delimiter in byte or character mode
+fn join_ranges<T: AsRef<[u8]>>(
+    positions: &[Range<usize>],
+    delimiter: &str,
+    extract: impl Fn(&[Range<usize>]) -> T,
+) -> Vec<u8> {
+    positions
         .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| chars.get(i)))
-        .collect()
+        .map(|range| extract(std::slice::from_ref(range)))
+        .filter(|piece| !piece.as_ref().is_empty())
+        .map(|piece| piece.as_ref().to_vec())
+        .collect::<Vec<_>>()
+        .join(delimiter.as_bytes())
 }
 
-pub fn extract_bytes(line: &str, byte_pos: &[Range<usize>]) -> String {
-    let bytes = line.as_bytes();
+// The positions below `len` that are not in `positions`, in ascending order
+pub fn complement(positions: &[Range<usize>], len: usize) -> PositionList {
+    let mut ranges: PositionList = vec![];
+
+    for i in (0..len).filter(|i| !positions.iter().any(|range| range.contains(i))) {
+        match ranges.last_mut() {
+            Some(last) if last.end == i => last.end = i + 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+
+    ranges
+}
+
+pub fn extract_chars(line: &str, char_pos: &[Range<usize>]) -> String {
+    let ascending = char_pos.windows(2).all(|pair| pair[0].end <= pair[1].start);
+    if !ascending {
+        // With --no-reorder positions can go backwards or repeat, so each
+        // range gets its own pass
+        return char_pos
+            .iter()
+            .map(|range| extract_chars(line, std::slice::from_ref(range)))
+            .collect();
+    }
+
+    // One pass over the line that stops after the last requested position
+    let mut selected = String::new();
+    let mut ranges = char_pos.iter().filter(|range| !range.is_empty()).peekable();
+    for (i, c) in line.chars().enumerate() {
+        while ranges.next_if(|range| range.end <= i).is_some() {}
+        match ranges.peek() {
+            None => break,
+            Some(range) if range.start <= i => selected.push(c),
+            Some(_) => (),
+        }
+    }
+
+    selected
+}
 
-    let selected: Vec<_> = byte_pos
+pub fn extract_raw_bytes(bytes: &[u8], byte_pos: &[Range<usize>]) -> Vec<u8> {
+    byte_pos
         .iter()
         .cloned()
         .flat_map(|range| range.filter_map(|i| bytes.get(i)))
         .copied()
-        .collect();
+        .collect()
+}
+
+// Where the first selected range cuts into a multibyte character, as an
+// offset into the record. Only records that are UTF-8 text are checked.
+pub fn split_char_offset(record: &[u8], byte_pos: &[Range<usize>]) -> Option<usize> {
+    std::str::from_utf8(record).ok()?;
+    byte_pos.iter().find_map(|range| {
+        let start = range.start.min(record.len());
+        let end = range.end.min(record.len());
+        std::str::from_utf8(&record[start..end])
+            .err()
+            .map(|err| start + err.valid_up_to())
+    })
+}
 
-    String::from_utf8_lossy(&selected).into_owned()
+pub fn extract_bytes(line: &str, byte_pos: &[Range<usize>]) -> String {
+    String::from_utf8_lossy(&extract_raw_bytes(line.as_bytes(), byte_pos)).into_owned()
 }
 
 pub fn extract_fields<'a>(record: &'a StringRecord, field_pos: &[Range<usize>]) -> Vec<&'a str> {
@@ -224,9 +992,41 @@ pub fn extract_fields<'a>(record: &'a StringRecord, field_pos: &[Range<usize>])
         .collect()
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+pub fn extract_split_fields<'a, T: ?Sized>(
+    fields: &[&'a T],
+    field_pos: &[Range<usize>],
+) -> Vec<&'a T> {
+    field_pos
+        .iter()
+        .cloned()
+        .flat_map(|range| range.filter_map(|i| fields.get(i).copied()))
+        .collect()
+}
+
+// Splits a raw record on every delimiter byte, quotes included
+pub fn split_raw_fields(record: &[u8], delimiter: u8) -> Vec<&[u8]> {
+    let mut fields = vec![];
+    let mut start = 0;
+    for end in memchr_iter(delimiter, record) {
+        fields.push(&record[start..end]);
+        start = end + 1;
+    }
+    fields.push(&record[start..]);
+    fields
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+fn open(filename: &str, force_gzip: bool) -> MyResult<Box<dyn BufRead>> {
+    let source: Box<dyn Read> = match filename {
+        "-" => Box::new(io::stdin()),
+        _ => Box::new(File::open(filename)?),
+    };
+    let mut reader = BufReader::new(source);
+
+    if force_gzip || reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
     }
 }
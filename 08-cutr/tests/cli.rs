@@ -1,6 +1,13 @@
+// Position lists are slices of ranges, so one-range slices are intended
+#![allow(clippy::single_range_in_vec_init)]
+
 use assert_cmd::Command;
 use csv::StringRecord;
-use cutr::{extract_bytes, extract_chars, extract_fields};
+use cutr::{
+    complement, extract_bytes, extract_chars, extract_fields, extract_raw_bytes,
+    extract_split_fields, normalize_positions, parse_line_range, parse_pos, resolve, resolve_names,
+    split_char_offset, split_raw_fields,
+};
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs;
@@ -11,6 +18,20 @@ const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const COLUMNS: &str = "tests/inputs/columns.tsv";
+const LINES: &str = "tests/inputs/lines.txt";
+const MIXED: &str = "tests/inputs/mixed.tsv";
+const LS: &str = "tests/inputs/ls.txt";
+const COLONS: &str = "tests/inputs/colons.txt";
+const SEPARATORS: &str = "tests/inputs/separators.txt";
+const PEOPLE: &str = "tests/inputs/people.csv";
+const BINARY: &str = "tests/inputs/binary.bin";
+const TSV_GZ: &str = "tests/inputs/movies1.tsv.gz";
+const CORRUPT_GZ: &str = "tests/inputs/corrupt.tsv.gz";
+const RAGGED: &str = "tests/inputs/ragged.tsv";
+const QUOTES: &str = "tests/inputs/quotes.csv";
+const CSV2: &str = "tests/inputs/movies2.csv";
+const ACCENTS: &str = "tests/inputs/accents.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -35,12 +56,15 @@ fn gen_bad_file() -> String {
 #[test]
 fn skips_bad_file() -> TestResult {
     let bad = gen_bad_file();
-    let expected = format!("{}: .* [(]os error 2[)]", bad);
+    let expected = format!("^cutr: {}: .* [(]os error 2[)]\n$", bad);
+    let stdout = fs::read_to_string("tests/expected/movies1.tsv.f1.out")?.repeat(2);
     Command::cargo_bin(PRG)?
-        .args(&["-f", "1", CSV, &bad, TSV])
+        .args(["-f", "1", TSV, &bad, TSV])
         .assert()
-        .success()
-        .stderr(predicate::str::is_match(expected)?);
+        .failure()
+        .code(1)
+        .stderr(predicate::str::is_match(expected)?)
+        .stdout(stdout);
     Ok(())
 }
 
@@ -81,6 +105,30 @@ fn dies_bad_digit_chars() -> TestResult {
     dies(&[CSV, "-c", &bad], &format!("illegal list value: {}", &bad))
 }
 
+// --------------------------------------------------
+fn dies_cleanly(args: &[&str], expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(expected))
+        .stderr(predicate::str::contains("panicked").not());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_list_without_panic() -> TestResult {
+    dies_cleanly(&[CSV, "-f", "0"], "illegal list value: 0")?;
+    dies_cleanly(&[CSV, "-f", "a-b"], "illegal list value: a-b")?;
+    dies_cleanly(
+        &[CSV, "-f", "3-1"],
+        "First number in range (3) must be lower than the second number (1)",
+    )?;
+    dies_cleanly(&[CSV, "-b", ""], "illegal list value: ")?;
+    dies_cleanly(&[CSV, "-c", "1,x"], "illegal list value: x")
+}
+
 // --------------------------------------------------
 #[test]
 fn dies_empty_delimiter() -> TestResult {
@@ -103,7 +151,7 @@ fn dies_bad_delimiter() -> TestResult {
 #[test]
 fn dies_chars_bytes_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-f", "1", "-b", "1"])
+        .args([CSV, "-c", "1", "-f", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -113,7 +161,7 @@ fn dies_chars_bytes_fields() -> TestResult {
 #[test]
 fn dies_bytes_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-f", "1", "-b", "1"])
+        .args([CSV, "-f", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -123,7 +171,7 @@ fn dies_bytes_fields() -> TestResult {
 #[test]
 fn dies_chars_fields() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-f", "1"])
+        .args([CSV, "-c", "1", "-f", "1"])
         .assert()
         .failure();
     Ok(())
@@ -133,7 +181,7 @@ fn dies_chars_fields() -> TestResult {
 #[test]
 fn dies_chars_bytes() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&[CSV, "-c", "1", "-b", "1"])
+        .args([CSV, "-c", "1", "-b", "1"])
         .assert()
         .failure();
     Ok(())
@@ -152,14 +200,13 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
 }
 
 // --------------------------------------------------
-fn run_lossy(args: &[&str], expected_file: &str) -> TestResult {
-    let contents = fs::read(expected_file)?;
-    let expected = String::from_utf8_lossy(&contents);
+fn run_bytes(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read(expected_file)?;
     Command::cargo_bin(PRG)?
         .args(args)
         .assert()
         .success()
-        .stdout(predicate::str::contains(expected));
+        .stdout(expected);
     Ok(())
 }
 
@@ -268,7 +315,7 @@ fn tsv_b2() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn tsv_b8() -> TestResult {
-    run_lossy(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
+    run_bytes(&[TSV, "-b", "8"], "tests/expected/movies1.tsv.b8.out")
 }
 
 // --------------------------------------------------
@@ -286,7 +333,7 @@ fn tsv_b2_3() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn tsv_b1_8() -> TestResult {
-    run_lossy(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
+    run_bytes(&[TSV, "-b", "1-8"], "tests/expected/movies1.tsv.b1-8.out")
 }
 
 // --------------------------------------------------
@@ -328,7 +375,849 @@ fn tsv_c1_8() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn repeated_value() -> TestResult {
-    run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
+    run(
+        &[BOOKS, "-c", "1,1", "--no-reorder"],
+        "tests/expected/books.c1,1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn repeated_value_once() -> TestResult {
+    run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn overlapping_ranges_once() -> TestResult {
+    let input = b"1\t2\t3\t4\t5\t6\t7\t8\n";
+    let expected = b"1\t2\t3\t4\t5\t6\t7\n";
+    run_stdin(&["-f", "1-5,3-7,2"], input, expected)?;
+    run_stdin(&["-f", "1-7"], input, expected)?;
+    run_stdin(&["-c", "1-5,3-7,2"], b"abcdefgh\n", b"abcdefg\n")?;
+    run_stdin(&["-b", "2,1-3,1-2"], b"abcdefgh\n", b"abc\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f3_1_1() -> TestResult {
+    run(
+        &[TSV, "-f", "3,1,1"],
+        "tests/expected/movies1.tsv.f3,1,1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f3_1_1_no_reorder() -> TestResult {
+    run(
+        &[TSV, "-f", "3,1,1", "--no-reorder"],
+        "tests/expected/movies1.tsv.f3,1,1.no-reorder.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_overlapping() -> TestResult {
+    run(
+        &[TSV, "-c", "5-8,1-6,2"],
+        "tests/expected/movies1.tsv.c1-8.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn complement_fields() -> TestResult {
+    run(
+        &[COLUMNS, "-f", "2", "--complement"],
+        "tests/expected/columns.tsv.f2.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn complement_chars() -> TestResult {
+    run(
+        &[LINES, "-c", "1-3", "--complement"],
+        "tests/expected/lines.txt.c1-3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn complement_bytes() -> TestResult {
+    run_bytes(
+        &[LINES, "-b", "2,4-5", "--complement"],
+        "tests/expected/lines.txt.b2,4-5.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_f1() -> TestResult {
+    run(&[MIXED, "-f", "1"], "tests/expected/mixed.tsv.f1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_f1_only_delimited() -> TestResult {
+    run(
+        &[MIXED, "-f", "1", "-s"],
+        "tests/expected/mixed.tsv.f1.s.out",
+    )?;
+    run(
+        &[MIXED, "-f", "1", "--only-delimited"],
+        "tests/expected/mixed.tsv.f1.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_f1_5_9() -> TestResult {
+    run(
+        &[LS, "-w", "-f", "1,5,9"],
+        "tests/expected/ls.txt.w.f1,5,9.out",
+    )?;
+    run(
+        &[LS, "--whitespace", "-f", "1,5,9"],
+        "tests/expected/ls.txt.w.f1,5,9.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_output_delimiter() -> TestResult {
+    run(
+        &[LS, "-w", "-f", "1,5,9", "--output-delimiter", ","],
+        "tests/expected/ls.txt.w.f1,5,9.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_whitespace_with_delimiter() -> TestResult {
+    dies(&[LS, "-w", "-d", ",", "-f", "1"], "cannot be used with")
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_delimiter_colons() -> TestResult {
+    run(
+        &[COLONS, "--regex-delimiter", "::", "-f", "1,3"],
+        "tests/expected/colons.txt.f1,3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn regex_delimiter_class() -> TestResult {
+    run(
+        &[
+            SEPARATORS,
+            "--regex-delimiter",
+            "[,;]",
+            "-f",
+            "2-3",
+            "--output-delimiter",
+            ",",
+        ],
+        "tests/expected/separators.txt.f2-3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_regex_delimiter() -> TestResult {
+    dies(
+        &[COLONS, "--regex-delimiter", "(", "-f", "1"],
+        r#"--regex-delimiter "(" is not a valid regex"#,
+    )?;
+    dies(
+        &[COLONS, "--regex-delimiter", ":", "-w", "-f", "1"],
+        "cannot be used with",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_string_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", ", "],
+        "tests/expected/movies1.tsv.f1,3.dcomma-space.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c1_2_5_6_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-c", "1-2,5-6", "--output-delimiter", "..."],
+        "tests/expected/movies1.tsv.c1-2,5-6.ddots.out",
+    )?;
+    run(
+        &[LINES, "-b", "1-2,5-6", "--output-delimiter", "..."],
+        "tests/expected/lines.txt.b1-2,5-6.ddots.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn names() -> TestResult {
+    run(
+        &[PEOPLE, "-d", ",", "--names", "email,name"],
+        "tests/expected/people.csv.names-email,name.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn names_no_header_out() -> TestResult {
+    run(
+        &[
+            PEOPLE,
+            "-d",
+            ",",
+            "--names",
+            "email,name",
+            "--no-header-out",
+        ],
+        "tests/expected/people.csv.names-email,name.no-header.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_names() -> TestResult {
+    dies(
+        &[PEOPLE, "-d", ",", "--names", "phone"],
+        "appears more than once in the header",
+    )?;
+    dies(
+        &[PEOPLE, "-d", ",", "--names", "name,age,zip"],
+        r#"unknown field name(s) "age", "zip", available: name, email, phone, phone"#,
+    )?;
+    dies(
+        &[PEOPLE, "--names", "name", "-f", "1"],
+        "cannot be used with",
+    )
+}
+
+// --------------------------------------------------
+fn run_stdin(args: &[&str], input: &[u8], expected: &[u8]) -> TestResult {
+    let output = Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated_fields() -> TestResult {
+    let input = b"a b\tc\0multi\nline\tname\0";
+    run_stdin(&["-f", "1", "-z"], input, b"a b\0multi\nline\0")?;
+    run_stdin(
+        &["-f", "2", "--zero-terminated", "--output-delimiter", "::"],
+        input,
+        b"c\0name\0",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated_chars() -> TestResult {
+    run_stdin(
+        &["-c", "1-3", "-z"],
+        b"hello\0wo\nrld\0\0",
+        b"hel\0wo\n\0\0",
+    )?;
+    run_stdin(&["-b", "1-3", "-z"], b"hello\0wo\nrld\0", b"hel\0wo\n\0")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_bytes() -> TestResult {
+    run_bytes(&[BINARY, "-b", "1,3"], "tests/expected/binary.bin.b1,3.out")?;
+    run_bytes(
+        &[BINARY, "-b", "2-3", "--complement"],
+        "tests/expected/binary.bin.b2-3.complement.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_output_is_input() -> TestResult {
+    let dir = std::env::temp_dir().join(format!("cutr-{}", random_string()));
+    fs::create_dir(&dir)?;
+    let data = dir.join("data.tsv");
+    fs::copy(TSV, &data)?;
+    let data = data.to_str().unwrap();
+
+    // Also caught when the two paths are spelled differently
+    let other_spelling = dir.join(".").join("data.tsv");
+    for output in [data, other_spelling.to_str().unwrap()] {
+        dies(
+            &["-f", "1", data, "-o", output],
+            &format!(r#"output file "{}" is also an input file"#, output),
+        )?;
+        assert_eq!(fs::read_to_string(data)?, fs::read_to_string(TSV)?);
+    }
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn append() -> TestResult {
+    let dir = std::env::temp_dir().join(format!("cutr-{}", random_string()));
+    fs::create_dir(&dir)?;
+    let out = dir.join("out.tsv");
+    let out = out.to_str().unwrap();
+
+    for _ in 0..2 {
+        Command::cargo_bin(PRG)?
+            .args([TSV, "-f", "1", "-o", out, "--append"])
+            .assert()
+            .success();
+    }
+    let expected = fs::read_to_string("tests/expected/movies1.tsv.f1.out")?;
+    assert_eq!(fs::read_to_string(out)?, expected.repeat(2));
+
+    // Without --append the output is replaced
+    Command::cargo_bin(PRG)?
+        .args([TSV, "-f", "1", "-o", out])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(out)?, expected);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn gzip_input() -> TestResult {
+    run(
+        &[TSV_GZ, "-f", "1-2"],
+        "tests/expected/movies1.tsv.f1-2.out",
+    )?;
+    run(
+        &[TSV_GZ, "-c", "1-8"],
+        "tests/expected/movies1.tsv.c1-8.out",
+    )?;
+    run_bytes(
+        &[TSV_GZ, "-b", "1-8"],
+        "tests/expected/movies1.tsv.b1-8.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn gzip_flag_on_stdin() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/movies1.tsv.f2.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-f", "2", "--gzip"])
+        .write_stdin(fs::read(TSV_GZ)?)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_corrupt_gzip() -> TestResult {
+    for mode in ["f", "c", "b"] {
+        let expected = fs::read_to_string(format!("tests/expected/movies1.tsv.{}1.out", mode))?;
+        Command::cargo_bin(PRG)?
+            .args([CORRUPT_GZ, TSV, &format!("-{}", mode), "1"])
+            .assert()
+            .code(1)
+            .stderr(format!("cutr: {}: unexpected end of file\n", CORRUPT_GZ))
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fields_from_end() -> TestResult {
+    let input = fs::read(RAGGED)?;
+    run_stdin(&["-f", "~1"], &input, b"c\ne\nf\nj\n")?;
+    run_stdin(&["-f", "~2-~1"], &input, b"b\tc\nd\te\nf\ni\tj\n")?;
+    run_stdin(&["-f", "2-~1"], &input, b"b\tc\ne\n\nh\ti\tj\n")?;
+    run_stdin(&["-f", "~3"], &input, b"a\n\n\nh\n")?;
+    run_stdin(&["-f", "~1", "--complement"], &input, b"a\tb\nd\n\ng\th\ti\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn chars_and_bytes_from_end() -> TestResult {
+    run_stdin(&["-c", "~3-~1"], "héllo\nab\n".as_bytes(), b"llo\nab\n")?;
+    run_stdin(&["-c", "1,~1"], "héllo\nab\n".as_bytes(), b"ho\nab\n")?;
+    run_stdin(&["-b", "~2-~1"], b"abc\nx\n", b"bc\nx\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_from_end() -> TestResult {
+    dies(&[CSV, "-f", "~0"], "illegal list value: ~0")?;
+    dies(&[CSV, "-f", "~a"], "illegal list value: ~a")?;
+    dies(
+        &[CSV, "-f", "~1-~2"],
+        "First number in range (~1) must be lower than the second number (~2)",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn quote_styles() -> TestResult {
+    for style in ["never", "always", "necessary"] {
+        run(
+            &[QUOTES, "-d", ",", "-f", "2,3", "--quote-style", style],
+            &format!("tests/expected/quotes.csv.f2,3.{}.out", style),
+        )?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn keep_quotes() -> TestResult {
+    run(
+        &[QUOTES, "-d", ",", "-f", "2,3", "--keep-quotes"],
+        "tests/expected/quotes.csv.f2,3.keep-quotes.out",
+    )?;
+    run(
+        &[
+            QUOTES,
+            "-d",
+            ",",
+            "-f",
+            "2-4",
+            "--keep-quotes",
+            "--quote-style",
+            "necessary",
+        ],
+        "tests/expected/quotes.csv.f2-4.keep-quotes.necessary.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_quoting() -> TestResult {
+    dies(
+        &[QUOTES, "-f", "1", "--quote-style", "sometimes"],
+        "invalid value",
+    )?;
+    dies(
+        &[QUOTES, "-w", "-f", "1", "--keep-quotes"],
+        "cannot be used with",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn header_every_file() -> TestResult {
+    let expected = "tests/expected/movies1,2.csv.f1,3.out";
+    run(&[CSV, CSV2, "-d", ",", "-f", "1,3"], expected)?;
+    run(&[CSV, CSV2, "-d", ",", "-f", "1,3", "--header"], expected)
+}
+
+// --------------------------------------------------
+#[test]
+fn header_only_first() -> TestResult {
+    let args = [CSV, CSV2, "-d", ",", "-f", "1,3", "--header-only-first"];
+    run(
+        &args,
+        "tests/expected/movies1,2.csv.f1,3.header-only-first.out",
+    )?;
+
+    let output = Command::cargo_bin(PRG)?.args(args).output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(stdout.matches("title,director").count(), 1);
+
+    let output = Command::cargo_bin(PRG)?
+        .args([
+            CSV,
+            CSV2,
+            "-d",
+            ",",
+            "--names",
+            "year",
+            "--header-only-first",
+        ])
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(stdout.lines().filter(|line| *line == "year").count(), 1);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_header_only_first() -> TestResult {
+    dies(
+        &[CSV, "-c", "1", "--header-only-first"],
+        "cannot be used with",
+    )?;
+    dies(
+        &[CSV, "-f", "1", "--header", "--header-only-first"],
+        "cannot be used with",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn lines_range() -> TestResult {
+    // The same as cutting everything and piping through head and tail
+    for (args, lines) in [
+        (["-f", "2"], 1..3),
+        (["-c", "2-5"], 2..4),
+        (["-b", "~3-~1"], 0..1),
+    ] {
+        let all = Command::cargo_bin(PRG)?.args(args).arg(BOOKS).output()?;
+        let all = String::from_utf8(all.stdout)?;
+        let expected: String = all
+            .lines()
+            .skip(lines.start)
+            .take(lines.len())
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        let range = format!("{}:{}", lines.start + 1, lines.end);
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .args([BOOKS, "--lines", &range])
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn lines_open_ends() -> TestResult {
+    let input = b"1\ta\n2\tb\n3\tc\n";
+    run_stdin(&["-f", "2", "--lines", "2:"], input, b"b\nc\n")?;
+    run_stdin(&["-f", "2", "--lines", ":1"], input, b"a\n")?;
+    run_stdin(&["-f", "2", "--lines", ":"], input, b"a\nb\nc\n")?;
+    run_stdin(&["-f", "2", "--lines", "5:9"], input, b"")?;
+    run_stdin(
+        &[
+            "-d",
+            ",",
+            "-f",
+            "1",
+            "--lines",
+            "1:1",
+            "--header-only-first",
+            CSV,
+            CSV2,
+        ],
+        b"",
+        b"title\nThe Blues Brothers\nThe Blues Brothers\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn lines_stop_reading() -> TestResult {
+    // Input that never ends is only read up to the last line wanted
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-c", "1", "--lines", "2:3"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || {
+        use std::io::Write;
+        while stdin.write_all(b"xyz\n").is_ok() {}
+    });
+
+    let output = child.wait_with_output()?;
+    writer.join().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"x\nx\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_lines() -> TestResult {
+    dies(&[BOOKS, "-f", "1", "--lines", "3"], "illegal line range: 3")?;
+    dies(
+        &[BOOKS, "-f", "1", "--lines", "0:3"],
+        "illegal line range: 0:3",
+    )?;
+    dies(
+        &[BOOKS, "-f", "1", "--lines", "5:3"],
+        "First line (5) must not be after the last line (3)",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_among_files() -> TestResult {
+    let input = b"stdin\tfirst\n";
+    let tsv = fs::read_to_string("tests/expected/movies1.tsv.f1.out")?;
+
+    run_stdin(
+        &["-f", "1", "-", TSV],
+        input,
+        format!("stdin\n{}", tsv).as_bytes(),
+    )?;
+    run_stdin(
+        &["-f", "1", TSV, "-"],
+        input,
+        format!("{}stdin\n", tsv).as_bytes(),
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_stdin_twice() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "1", "-", TSV, "-"])
+        .write_stdin("a\n")
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("cutr: standard input specified more than once\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_quote_same_as_csv() -> TestResult {
+    // Without quotes in the input both field splitters agree
+    for file in [TSV, BOOKS, COLUMNS] {
+        for list in ["1", "2", "1,3", "~1", "3-~1"] {
+            for extra in [
+                &[][..],
+                &["--complement"],
+                &["-s"],
+                &["--output-delimiter", ", "],
+            ] {
+                let output = Command::cargo_bin(PRG)?
+                    .args([file, "-f", list])
+                    .args(extra)
+                    .output()?;
+                assert!(output.status.success());
+                Command::cargo_bin(PRG)?
+                    .args([file, "-f", list, "--no-quote"])
+                    .args(extra)
+                    .assert()
+                    .success()
+                    .stdout(output.stdout);
+            }
+        }
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_quote_lone_quote() -> TestResult {
+    let input = b"say \"hi\tbye\"\n5\"\t6 ft\n";
+    run_stdin(&["-f", "2", "--no-quote"], input, b"bye\"\n6 ft\n")?;
+    run_stdin(&["-f", "1", "--no-quote"], input, b"say \"hi\n5\"\n")?;
+    run_stdin(
+        &["-f", "1", "--no-quote", "-d", "-"],
+        b"\xff\"-x\n",
+        b"\xff\"\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn no_quote_crlf() -> TestResult {
+    let input = b"a\tb\r\nc\td\r\n";
+    run_stdin(&["-f", "2", "--no-quote"], input, b"b\nd\n")?;
+    run_stdin(&["-f", "2"], input, b"b\nd\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn line_buffered() -> TestResult {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Command as StdCommand, Stdio};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    for args in [
+        &["-f", "2"][..],
+        &["-f", "2", "--output-delimiter", "::"],
+        &["-f", "2", "--no-quote"],
+        &["-c", "3"],
+        &["-b", "3"],
+    ] {
+        let mut child = StdCommand::new(assert_cmd::cargo::cargo_bin(PRG))
+            .args(args)
+            .arg("--line-buffered")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let (tx, rx) = mpsc::channel();
+        let reader = std::thread::spawn(move || {
+            for line in stdout.lines() {
+                tx.send(line.unwrap()).unwrap();
+            }
+        });
+
+        // Each record must come out before the next one goes in
+        for record in ["a\tb\n", "c\td\n"] {
+            stdin.write_all(record.as_bytes())?;
+            stdin.flush()?;
+            let line = rx.recv_timeout(Duration::from_secs(10))?;
+            assert_eq!(line.as_bytes(), &record.as_bytes()[2..3], "{:?}", args);
+        }
+
+        drop(stdin);
+        assert!(child.wait()?.success());
+        reader.join().unwrap();
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn strict_utf8_error() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([ACCENTS, ACCENTS, "-b", "1-2", "--strict-utf8"])
+        .assert()
+        .code(1)
+        .stdout("ab\nab\n")
+        .stderr(format!(
+            "cutr: {0}: line 2: selection splits a multibyte character at byte 2\n\
+             cutr: {0}: line 2: selection splits a multibyte character at byte 2\n",
+            ACCENTS
+        ));
+
+    // Selections on character boundaries are fine
+    Command::cargo_bin(PRG)?
+        .args([ACCENTS, "-b", "1-3", "--strict-utf8"])
+        .assert()
+        .success()
+        .stdout("abc\naé\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn strict_utf8_warn() -> TestResult {
+    let output = Command::cargo_bin(PRG)?
+        .args([ACCENTS, "-b", "1-2", "--strict-utf8=warn"])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"ab\na\xc3\n");
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        format!(
+            "cutr: warning: {}: line 2: selection splits a multibyte character at byte 2\n",
+            ACCENTS
+        )
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_strict_utf8() -> TestResult {
+    dies(
+        &[ACCENTS, "-c", "1", "--strict-utf8"],
+        "cannot be used with",
+    )?;
+    dies(&[ACCENTS, "-b", "1", "--strict-utf8=loud"], "invalid value")
+}
+
+#[test]
+fn test_split_char_offset() {
+    let text = "aéb".as_bytes();
+    assert_eq!(split_char_offset(text, &[0..2]), Some(1));
+    assert_eq!(split_char_offset(text, &[2..4]), Some(2));
+    assert_eq!(split_char_offset(text, &[0..1, 3..4]), None);
+    assert_eq!(split_char_offset(text, &[1..3, 3..9]), None);
+    // Only UTF-8 text is checked
+    assert_eq!(split_char_offset(b"a\xff\xc3\xa9", &[0..3]), None);
+}
+
+#[test]
+fn test_parse_line_range() {
+    assert_eq!(parse_line_range("1:3"), Ok(0..3));
+    assert_eq!(parse_line_range("4:4"), Ok(3..4));
+    assert_eq!(parse_line_range("2:"), Ok(1..usize::MAX));
+    assert_eq!(parse_line_range(":2"), Ok(0..2));
+    assert_eq!(parse_line_range(":"), Ok(0..usize::MAX));
+    assert!(parse_line_range("").is_err());
+    assert!(parse_line_range("0:1").is_err());
+    assert!(parse_line_range("1:0").is_err());
+    assert!(parse_line_range("+1:2").is_err());
+    assert!(parse_line_range("a:b").is_err());
+    assert!(parse_line_range("3:2").is_err());
+}
+
+#[test]
+fn test_resolve_names() {
+    let header = StringRecord::from(vec!["id", "name", "email", "id"]);
+    let names = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(
+        resolve_names(&header, &names(&["email", "name"])),
+        Ok(vec![2..3, 1..2])
+    );
+    assert!(resolve_names(&header, &names(&["id"])).is_err());
+    assert_eq!(
+        resolve_names(&header, &names(&["nope"])),
+        Err(r#"unknown field name(s) "nope", available: id, name, email, id"#.to_string())
+    );
+}
+
+#[test]
+fn test_normalize_positions() {
+    // Repeated single positions
+    assert_eq!(
+        normalize_positions(vec![2..3, 0..1, 0..1]),
+        vec![0..1, 2..3]
+    );
+    // Contained ranges
+    assert_eq!(normalize_positions(vec![4..8, 0..6, 1..2]), vec![0..8]);
+    assert_eq!(normalize_positions(vec![0..7, 2..3, 1..5]), vec![0..7]);
+    // Overlapping ranges, as in 1-5,3-7,2
+    assert_eq!(normalize_positions(vec![0..5, 2..7, 1..2]), vec![0..7]);
+    assert_eq!(normalize_positions(vec![2..4, 0..3]), vec![0..4]);
+    // Touching ranges stay apart for --output-delimiter
+    assert_eq!(normalize_positions(vec![3..4, 0..3]), vec![0..3, 3..4]);
+    // Disjoint ranges
+    assert_eq!(normalize_positions(vec![5..6, 0..2]), vec![0..2, 5..6]);
+    assert!(normalize_positions(vec![]).is_empty());
+}
+
+#[test]
+fn test_resolve() {
+    let resolve = |list: &str, len| resolve(&parse_pos(list).unwrap(), len);
+    assert_eq!(resolve("1,~1", 3), vec![0..1, 2..3]);
+    assert_eq!(resolve("~2-~1", 5), vec![3..5]);
+    assert_eq!(resolve("~4-~1", 2), vec![0..2]);
+    assert_eq!(resolve("2-~1", 4), vec![1..4]);
+    assert!(resolve("3-~1", 2).is_empty());
+    assert!(resolve("~3", 2).is_empty());
+    assert!(resolve("~1", 0).is_empty());
+}
+
+#[test]
+fn test_complement() {
+    assert_eq!(complement(&[1..2], 4), vec![0..1, 2..4]);
+    assert!(complement(&[0..3], 2).is_empty());
+    assert_eq!(complement(&[0..3], 5), vec![3..5]);
+    assert_eq!(complement(&[3..4, 0..1], 5), vec![1..3, 4..5]);
+    assert_eq!(complement(&[], 2), vec![0..2]);
 }
 
 #[test]
@@ -341,6 +1230,38 @@ fn test_extract_chars() {
     assert_eq!(extract_chars("ábc", &[0..1, 1..2, 4..5]), "áb".to_string());
 }
 
+#[test]
+fn test_extract_chars_multibyte_boundaries() {
+    let line = "aéb€c😀d";
+    assert_eq!(extract_chars(line, &[1..2]), "é");
+    assert_eq!(extract_chars(line, &[0..2, 3..4]), "aé€");
+    assert_eq!(extract_chars(line, &[3..6]), "€c😀");
+    assert_eq!(extract_chars(line, &[5..6, 6..10]), "😀d");
+    assert_eq!(extract_chars(line, &[5..6, 1..2, 5..6]), "😀é😀");
+    assert_eq!(extract_chars(line, &[2..2, 4..5]), "c");
+    assert_eq!(extract_chars(line, &[]), "");
+}
+
+#[test]
+fn test_extract_chars_long_lines() {
+    // Only the leading chars are requested, so the rest of each line must
+    // not be walked
+    let line = "é".repeat(1_000_000);
+    let start = std::time::Instant::now();
+    for _ in 0..1_000 {
+        assert_eq!(extract_chars(&line, &[0..3]), "ééé");
+    }
+    assert!(start.elapsed() < std::time::Duration::from_secs(2));
+}
+
+#[test]
+fn test_extract_raw_bytes() {
+    let bytes = b"\xffab\xfe";
+    assert_eq!(extract_raw_bytes(bytes, &[0..1]), b"\xff");
+    assert_eq!(extract_raw_bytes(bytes, &[3..4, 0..2]), b"\xfe\xffa");
+    assert_eq!(extract_raw_bytes(bytes, &[2..9]), b"b\xfe");
+}
+
 #[test]
 fn test_extract_bytes() {
     assert_eq!(extract_bytes("ábc", &[0..1]), "�".to_string());
@@ -360,3 +1281,32 @@ fn test_extract_fields() {
     assert_eq!(extract_fields(&rec, &[0..1, 3..4]), &["Captain"]);
     assert_eq!(extract_fields(&rec, &[1..2, 0..1]), &["Sham", "Captain"]);
 }
+
+#[test]
+fn test_extract_split_fields() {
+    let fields = ["Captain", "Sham", "12345"];
+    assert_eq!(extract_split_fields(&fields, &[0..1]), &["Captain"]);
+    assert_eq!(
+        extract_split_fields(&fields, &[0..1, 2..3]),
+        &["Captain", "12345"]
+    );
+    assert_eq!(extract_split_fields(&fields, &[0..1, 3..4]), &["Captain"]);
+    assert_eq!(
+        extract_split_fields(&fields, &[1..2, 0..1]),
+        &["Sham", "Captain"]
+    );
+
+    let fields: [&[u8]; 3] = [b"a", b"\"b", b"\xff"];
+    assert_eq!(
+        extract_split_fields(&fields, &[1..3]),
+        [&b"\"b"[..], &b"\xff"[..]]
+    );
+}
+
+#[test]
+fn test_split_raw_fields() {
+    assert_eq!(split_raw_fields(b"a\tb\tc", b'\t'), [&b"a"[..], b"b", b"c"]);
+    assert_eq!(split_raw_fields(b"\"a\t\"b", b'\t'), [&b"\"a"[..], b"\"b"]);
+    assert_eq!(split_raw_fields(b"\t", b'\t'), [&b""[..], b""]);
+    assert_eq!(split_raw_fields(b"", b'\t'), [&b""[..]]);
+}
@@ -1,101 +1,851 @@
 use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
     error::Error,
     fs::{self, File},
-    io::{self, BufRead, BufReader},
-    mem,
+    io::{self, BufRead, BufReader, Cursor, IsTerminal},
+    iter,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
 };
 
-use clap::Parser;
-use regex::{Regex, RegexBuilder};
+use clap::{ArgAction, Parser, ValueEnum};
+use ignore::WalkBuilder;
+use memchr::{memchr, memchr_iter, memrchr};
+use memmap2::Mmap;
+use owo_colors::{OwoColorize, Style};
+use regex::{bytes, Regex, RegexBuilder};
 use walkdir::WalkDir;
-use owo_colors::{OwoColorize, colors::*};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+// How to treat files that look binary, that is have a NUL byte early on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BinaryFiles {
+    Binary,
+    Text,
+    WithoutMatch,
+}
+
+// The outcome of a search, which selects the exit status as in grep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Matched,
+    NoMatch,
+    Error,
+}
+
+impl Status {
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Matched => 0,
+            Status::NoMatch => 1,
+            Status::Error => 2,
+        }
+    }
+}
+
+// -h is for --no-filename, as in grep, so help is only --help
 #[derive(Debug, Parser)]
+#[command(disable_help_flag = true)]
 pub struct Args {
-    #[arg(help = "Pattern to match")]
-    pattern: String,
+    #[arg(
+        help = "Pattern to match, unless given with -e or -f",
+        required_unless_present_any = ["regexp", "pattern_file"]
+    )]
+    pattern: Option<String>,
 
-    #[arg(help = "Input files, defaults to STDIN", default_value = "-")]
+    #[arg(help = "Input files, defaults to STDIN")]
     files: Vec<String>,
 
+    #[arg(
+        help = "pattern to match, may be repeated",
+        short = 'e',
+        long = "regexp",
+        value_name = "PATTERN",
+        allow_hyphen_values = true
+    )]
+    regexp: Vec<String>,
+
+    #[arg(
+        help = "read patterns from FILE, one per line (an empty line matches every line)",
+        short = 'f',
+        long = "file",
+        value_name = "FILE"
+    )]
+    pattern_file: Vec<String>,
+
+    #[arg(
+        help = "suppress error messages about missing or unreadable files",
+        short = 's',
+        long
+    )]
+    no_messages: bool,
+
     #[arg(help = "search files in directories", short = 'r', long)]
     recursive: bool,
 
+    #[arg(
+        help = "with -r, skip hidden files and those ignored by .gitignore and .ignore",
+        long
+    )]
+    smart: bool,
+
+    #[arg(
+        help = "with -r, follow symbolic links to directories",
+        long,
+        visible_alias = "dereference-recursive"
+    )]
+    follow: bool,
+
+    #[arg(
+        help = "print nothing, exiting with status 0 at the first match",
+        short = 'q',
+        long,
+        alias = "silent"
+    )]
+    quiet: bool,
+
     #[arg(help = "show count of matches", short = 'c', long)]
     count: bool,
 
+    #[arg(
+        help = "with -c, end with a total:N line counting over all files",
+        long,
+        requires = "count"
+    )]
+    total: bool,
+
+    #[arg(
+        help = "stop reading a file after NUM selected lines",
+        short = 'm',
+        long,
+        value_name = "NUM"
+    )]
+    max_count: Option<usize>,
+
+    #[arg(
+        help = "only list files with a match",
+        short = 'l',
+        long,
+        conflicts_with_all = ["count", "files_without_match"]
+    )]
+    files_with_matches: bool,
+
+    #[arg(
+        help = "only list files without a match",
+        short = 'L',
+        long,
+        conflicts_with = "count"
+    )]
+    files_without_match: bool,
+
+    #[arg(
+        help = "print only the matched parts of lines, each on its own line",
+        short = 'o',
+        long
+    )]
+    only_matching: bool,
+
+    #[arg(
+        help = "with -o, print TEMPLATE for each match, with $1 or $name for its groups",
+        long,
+        value_name = "TEMPLATE",
+        requires = "only_matching",
+        allow_hyphen_values = true
+    )]
+    replace: Option<String>,
+
+    #[arg(
+        help = "print the filename for each match, even with one file",
+        short = 'H',
+        long,
+        conflicts_with = "no_filename"
+    )]
+    with_filename: bool,
+
+    #[arg(
+        help = "never print filenames for matches, even with many files",
+        short = 'h',
+        long
+    )]
+    no_filename: bool,
+
+    #[arg(help = "name standard input NAME in output", long, value_name = "NAME")]
+    label: Option<String>,
+
+    #[arg(help = "prefix lines with their line number", short = 'n', long)]
+    line_number: bool,
+
+    #[arg(
+        help = "prefix lines, or parts with -o, with their byte offset in the file",
+        short = 'b',
+        long
+    )]
+    byte_offset: bool,
+
+    #[arg(
+        help = "print NUM lines of context after each selected line",
+        short = 'A',
+        long,
+        value_name = "NUM"
+    )]
+    after_context: Option<usize>,
+
+    #[arg(
+        help = "print NUM lines of context before each selected line",
+        short = 'B',
+        long,
+        value_name = "NUM"
+    )]
+    before_context: Option<usize>,
+
+    #[arg(
+        help = "print NUM lines of context around each selected line",
+        short = 'C',
+        long,
+        value_name = "NUM"
+    )]
+    context: Option<usize>,
+
     #[arg(help = "invert matches", short = 'v', long)]
     invert_match: bool,
 
     #[arg(help = "case insensitive search", short = 'i', long)]
     insensitive: bool,
+
+    #[arg(
+        help = "only match whole words",
+        short = 'w',
+        long,
+        conflicts_with = "line_regexp"
+    )]
+    word_regexp: bool,
+
+    #[arg(help = "only match whole lines", short = 'x', long)]
+    line_regexp: bool,
+
+    #[arg(help = "match the pattern as a literal string", short = 'F', long)]
+    fixed_strings: bool,
+
+    #[arg(
+        help = "how to treat binary files",
+        long,
+        value_name = "TYPE",
+        value_enum,
+        default_value = "binary"
+    )]
+    binary_files: BinaryFiles,
+
+    #[arg(help = "treat binary files as text", short = 'a', long)]
+    text: bool,
+
+    #[arg(
+        help = "end filenames with a NUL byte instead of a newline or ':'",
+        short = 'Z',
+        long
+    )]
+    null: bool,
+
+    #[arg(
+        help = "lines in input and output end with a NUL byte, not a newline",
+        short = 'z',
+        long
+    )]
+    null_data: bool,
+
+    #[arg(
+        help = "number of files to search at once",
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    threads: u16,
+
+    #[arg(
+        help = "with --threads, print each file as soon as it is searched",
+        long
+    )]
+    unordered: bool,
+
+    #[arg(
+        help = "search regular files mapped into memory, which is faster for large ones",
+        long
+    )]
+    mmap: bool,
+
+    #[arg(help = "Print help", long, action = ArgAction::Help)]
+    help: Option<bool>,
+
+    #[arg(
+        help = "highlight matches, filenames and line numbers",
+        long,
+        alias = "colour",
+        value_name = "WHEN",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value = "auto",
+        default_missing_value = "auto"
+    )]
+    color: ColorChoice,
 }
 
 #[derive(Debug)]
 pub struct Config {
     pattern: Regex,
     files: Vec<String>,
+    no_messages: bool,
     recursive: bool,
+    smart: bool,
+    follow: bool,
+    quiet: bool,
     count: bool,
+    total: bool,
+    max_count: Option<usize>,
+    files_with_matches: bool,
+    files_without_match: bool,
+    only_matching: bool,
+    replace: Option<String>,
+    // Whether to prefix matches with the filename, by default only when
+    // there is more than one file
+    with_filename: Option<bool>,
+    label: Option<String>,
+    line_number: bool,
+    byte_offset: bool,
+    // The number of context lines before and after selected lines, if any
+    // were asked for. Even none sets groups of lines apart, as in grep.
+    context: Option<(usize, usize)>,
     invert_match: bool,
+    color: bool,
+    binary_files: BinaryFiles,
+    null: bool,
+    // What ends a line, NUL with -z
+    terminator: u8,
+    threads: usize,
+    unordered: bool,
+    // With --mmap, the pattern to search whole files with. Lines selected
+    // by -v, ended by NUL with -z, or shown with context are still read one
+    // at a time.
+    mmap_pattern: Option<bytes::Regex>,
 }
 
 pub fn get_args() -> MyResult<Config> {
     let args = Args::parse();
-
-    let pattern = RegexBuilder::new(&args.pattern)
-        .case_insensitive(args.insensitive)
-        .build()
-        .map_err(|_| format!("Invalid pattern \"{}\"", args.pattern))?;
+    let (patterns, files) = split_args(&args)?;
+    let pattern = build_pattern(&patterns, &args)?;
+    if let Some(template) = &args.replace {
+        check_template(template, &pattern)?;
+    }
+    let has_context = [args.after_context, args.before_context, args.context]
+        .iter()
+        .any(Option::is_some);
+    let mmap_pattern = if args.mmap && !args.invert_match && !args.null_data && !has_context {
+        // The same pattern, but with ^ and $ matching at every line
+        Some(
+            bytes::RegexBuilder::new(pattern.as_str())
+                .case_insensitive(args.insensitive)
+                .multi_line(true)
+                .build()?,
+        )
+    } else {
+        None
+    };
 
     Ok(Config {
         pattern,
-        files: args.files,
+        files,
+        no_messages: args.no_messages,
         recursive: args.recursive,
+        smart: args.smart,
+        follow: args.follow,
+        quiet: args.quiet,
         count: args.count,
+        total: args.total,
+        max_count: args.max_count,
+        files_with_matches: args.files_with_matches,
+        files_without_match: args.files_without_match,
+        only_matching: args.only_matching,
+        replace: args.replace.clone(),
+        with_filename: match (args.with_filename, args.no_filename) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        label: args.label,
+        line_number: args.line_number,
+        byte_offset: args.byte_offset,
+        // -o prints only parts of selected lines, so never context
+        context: if args.only_matching || !has_context {
+            None
+        } else {
+            Some((
+                args.before_context.or(args.context).unwrap_or(0),
+                args.after_context.or(args.context).unwrap_or(0),
+            ))
+        },
         invert_match: args.invert_match,
+        color: match args.color {
+            ColorChoice::Auto => io::stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        },
+        // NUL bytes are expected in input with -z, so nothing looks binary
+        binary_files: if args.text || args.null_data {
+            BinaryFiles::Text
+        } else {
+            args.binary_files
+        },
+        null: args.null,
+        terminator: if args.null_data { b'\0' } else { b'\n' },
+        threads: args.threads.into(),
+        unordered: args.unordered,
+        mmap_pattern,
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let entries = find_files(&config.files, config.recursive);
-    let num_files = entries.len();
+// Collects the patterns from -e and -f. When there are any, the first
+// positional argument is a file rather than the pattern.
+fn split_args(args: &Args) -> MyResult<(Vec<String>, Vec<String>)> {
+    let mut patterns = args.regexp.clone();
+    for pattern_file in &args.pattern_file {
+        let text =
+            fs::read_to_string(pattern_file).map_err(|e| format!("{}: {}", pattern_file, e))?;
+        patterns.extend(text.lines().map(String::from));
+    }
+
+    let mut files = args.files.clone();
+    if args.regexp.is_empty() && args.pattern_file.is_empty() {
+        patterns.extend(args.pattern.clone());
+    } else if let Some(file) = &args.pattern {
+        files.insert(0, file.clone());
+    }
+    if files.is_empty() {
+        files.push("-".to_string());
+    }
+
+    Ok((patterns, files))
+}
+
+// A line matches if any of the patterns does. Each one is grouped so that
+// alternation inside it stays inside, also within the -w/-x anchors.
+fn build_pattern(patterns: &[String], args: &Args) -> MyResult<Regex> {
+    let mut sources = vec![];
+    for pattern in patterns {
+        let source = pattern_source(pattern, args.fixed_strings);
+        Regex::new(&source).map_err(|_| format!("Invalid pattern \"{}\"", pattern))?;
+        sources.push(format!("(?:{})", source));
+    }
 
-    let print = |fname: &str, val: &str| {
-        if num_files > 1 {
-            print!("{}:{}", fname.fg::<Green>(), val);
+    // No patterns at all, as from an empty -f file, match no line
+    let pattern = if sources.is_empty() {
+        r"[^\s\S]".to_string()
+    } else {
+        sources.join("|")
+    };
+    let pattern = if args.word_regexp {
+        format!(r"\b(?:{})\b", pattern)
+    } else if args.line_regexp {
+        format!("^(?:{})$", pattern)
+    } else {
+        pattern
+    };
+
+    Ok(RegexBuilder::new(&pattern)
+        .case_insensitive(args.insensitive)
+        .build()?)
+}
+
+// A --replace template may only name groups that the pattern has, as
+// expanding it would leave out any others without a word. "$$" is a
+// literal "$", as is a "$" not followed by a name.
+fn check_template(template: &str, pattern: &Regex) -> MyResult<()> {
+    let mut rest = template;
+    while let Some(i) = rest.find('$') {
+        rest = &rest[i + 1..];
+        let name = if let Some(after) = rest.strip_prefix('$') {
+            rest = after;
+            continue;
+        } else if let Some((name, after)) = rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+            rest = after;
+            name
         } else {
-            print!("{}", val);
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let name = &rest[..end];
+            rest = &rest[end..];
+            name
+        };
+
+        let known = match name.parse::<usize>() {
+            Ok(index) => index < pattern.captures_len(),
+            Err(_) => name.is_empty() || pattern.capture_names().flatten().any(|n| n == name),
+        };
+        if !known {
+            return Err(From::from(format!(
+                "Invalid replacement \"{}\": no group {}",
+                template, name
+            )));
         }
+    }
+    Ok(())
+}
+
+// The regex source for one pattern as given by the user, escaped with -F
+fn pattern_source(pattern: &str, fixed_strings: bool) -> String {
+    if fixed_strings {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    }
+}
+
+pub fn run(config: Config) -> MyResult<Status> {
+    // The errors are kept as text so that the list can be shared by threads
+    let entries: Vec<Result<String, String>> =
+        find_files(&config.files, config.recursive, config.smart, config.follow)
+            .into_iter()
+            .map(|entry| entry.map_err(|e| e.to_string()))
+            .collect();
+    let show_filename = config.with_filename.unwrap_or(entries.len() > 1);
+
+    // Whether any line was selected, or any file listed with -l/-L
+    let mut found = false;
+    // Whether any file could not be searched
+    let mut failed = false;
+    // The number of lines selected in all files, for --total
+    let mut total = 0;
+    // Whether lines with context have been printed, to put "--" between
+    // them and those of the next file
+    let mut context_printed = false;
+    let lists_lines =
+        !(config.count || config.quiet || config.files_with_matches || config.files_without_match);
+    let separates = config.context.is_some() && lists_lines;
+
+    // Prints what one file gave, returning whether to go on to the next
+    let mut report = |search: Search| {
+        if !config.no_messages {
+            for e in &search.errors {
+                eprintln!("grepr: {}", e);
+            }
+        }
+        if separates && !search.out.is_empty() {
+            if context_printed {
+                println!("--");
+            }
+            context_printed = true;
+        }
+        print!("{}", search.out);
+        found |= search.found;
+        failed |= !search.errors.is_empty();
+        total += search.count;
+        // A match decides the status with -q, so there is no need to go on
+        !(config.quiet && search.found)
     };
 
-    for entry in entries {
-        match entry {
-            Err(e) => eprintln!("{}", e),
-            Ok(filename) => match open(&filename) {
-                Err(e) => eprintln!("{}: {}", filename, e),
-                Ok(file) => match find_lines(file, &config.pattern, config.invert_match) {
-                    Err(e) => eprintln!("{}", e),
-                    Ok(matches) => {
-                        if config.count {
-                            print(&filename, &format!("{}\n", matches.len()));
+    if config.threads > 1 {
+        search_parallel(&entries, &config, show_filename, report);
+    } else {
+        for entry in &entries {
+            if !report(search(entry, &config, show_filename)) {
+                break;
+            }
+        }
+    }
+
+    if config.total && !config.quiet {
+        println!("total:{}", total);
+    }
+
+    // An error outweighs any match, as in grep, except with -q
+    Ok(if config.quiet && found {
+        Status::Matched
+    } else if failed {
+        Status::Error
+    } else if found {
+        Status::Matched
+    } else {
+        Status::NoMatch
+    })
+}
+
+// What searching one file gives. The output is kept whole so that files
+// searched in parallel never interleave.
+#[derive(Debug, Default)]
+struct Search {
+    out: String,
+    errors: Vec<String>,
+    found: bool,
+    // The number of lines selected, with -c
+    count: usize,
+}
+
+// Searches the files with a pool of config.threads workers. Results are
+// reported in the order of the files unless --unordered is given.
+fn search_parallel<F>(
+    entries: &[Result<String, String>],
+    config: &Config,
+    show_filename: bool,
+    mut report: F,
+) where
+    F: FnMut(Search) -> bool,
+{
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..config.threads.min(entries.len()) {
+            let tx = tx.clone();
+            let (next, stop) = (&next, &stop);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= entries.len() || stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                if tx
+                    .send((i, search(&entries[i], config, show_filename)))
+                    .is_err()
+                {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // Results that finish ahead of their turn wait here
+        let mut pending = HashMap::new();
+        let mut turn = 0;
+        for (i, search) in rx {
+            if config.unordered {
+                if !report(search) {
+                    break;
+                }
+                continue;
+            }
+            pending.insert(i, search);
+            while let Some(search) = pending.remove(&turn) {
+                turn += 1;
+                if !report(search) {
+                    stop.store(true, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+}
+
+// Searches one file, or reports why it could not be
+fn search(entry: &Result<String, String>, config: &Config, show_filename: bool) -> Search {
+    let mut search = Search::default();
+    let filename = match entry {
+        Err(e) => {
+            search.errors.push(e.clone());
+            return search;
+        }
+        Ok(filename) => filename,
+    };
+    // How the file is named in all output and errors
+    let name = display_name(filename, config.label.as_deref());
+    let color = config.color;
+    let null = config.null;
+    let eol = char::from(config.terminator);
+
+    let file = match open_source(filename, config) {
+        Err(e) => {
+            search.errors.push(error_message(name, e.as_ref()));
+            return search;
+        }
+        // Nothing is read with -m 0, as in grep
+        Ok(_) if config.max_count == Some(0) => (Source::Stream(Box::new(io::empty())), false),
+        Ok(file) => file,
+    };
+    let out = &mut search.out;
+    match file {
+        (file, _) if config.quiet => match file.has_match(config) {
+            Err(e) => search.errors.push(error_message(name, e.as_ref())),
+            Ok(matched) => search.found = matched,
+        },
+        (file, _) if config.files_with_matches || config.files_without_match => {
+            match file.has_match(config) {
+                Err(e) => search.errors.push(error_message(name, e.as_ref())),
+                Ok(matched) => {
+                    if matched == config.files_with_matches {
+                        let end = if null { '\0' } else { '\n' };
+                        *out += &format!("{}{}", name, end);
+                        search.found = true;
+                    }
+                }
+            }
+        }
+        // Only say that a binary file matches rather than print its lines
+        (file, true) if !config.count => match file.has_match(config) {
+            Err(e) => search.errors.push(error_message(name, e.as_ref())),
+            Ok(matched) => {
+                if matched {
+                    *out += &format!("Binary file {} matches\n", name);
+                    search.found = true;
+                }
+            }
+        },
+        (file, _) if config.count => match file.count_lines(config) {
+            Err(e) => search.errors.push(error_message(name, e.as_ref())),
+            Ok(count) => {
+                search.found = count > 0;
+                search.count = count;
+                let filename = show_filename.then_some(name);
+                let prefix = format_prefix(filename, None, None, ':', null, color);
+                *out += &format!("{}{}\n", prefix, count);
+            }
+        },
+        (file, _) => match file.find_lines(config) {
+            Err(e) => search.errors.push(error_message(name, e.as_ref())),
+            Ok(lines) => {
+                search.found = lines.iter().any(|line| line.selected);
+                let filename = show_filename.then_some(name);
+                let mut last_num = None;
+                for line in &lines {
+                    // Groups of lines with context between them are set apart
+                    if config.context.is_some() && last_num.is_some_and(|num| num + 1 < line.num) {
+                        *out += "--\n";
+                    }
+                    last_num = Some(line.num);
+
+                    let line_num = config.line_number.then_some(line.num);
+                    if !config.only_matching {
+                        // Selected lines are marked with ':' and context with '-'
+                        let separator = if line.selected { ':' } else { '-' };
+                        let offset = config.byte_offset.then_some(line.offset);
+                        let prefix =
+                            format_prefix(filename, line_num, offset, separator, null, color);
+                        if color {
+                            *out +=
+                                &format!("{}{}", prefix, highlight(&config.pattern, &line.text));
                         } else {
-                            for line in &matches {
-                                print(&filename, line);
+                            *out += &format!("{}{}", prefix, line.text);
+                        }
+                    } else if !config.invert_match {
+                        // Lines selected by -v have no matched parts to print
+                        for (start, part) in
+                            matched_parts(&config.pattern, &line.text, config.replace.as_deref())
+                        {
+                            // With -o the offset is that of the part itself
+                            let offset = config.byte_offset.then_some(line.offset + start);
+                            let prefix =
+                                format_prefix(filename, line_num, offset, ':', null, color);
+                            if color {
+                                let part = part.style(match_style());
+                                *out += &format!("{}{}{}", prefix, part, eol);
+                            } else {
+                                *out += &format!("{}{}{}", prefix, part, eol);
                             }
                         }
                     }
-                },
-            },
+                }
+            }
+        },
+    }
+    search
+}
+
+// An error about a file as grep words it, such as "a.txt: No such file or
+// directory", without the "(os error 2)" that io::Error adds
+fn error_message(filename: &str, e: &dyn Error) -> String {
+    let message = e.to_string();
+    let message = match message.find(" (os error ") {
+        Some(end) => &message[..end],
+        None => &message,
+    };
+    format!("{}: {}", filename, message)
+}
+
+// How a file is named in output, with stdin as "(standard input)" unless
+// given a --label
+fn display_name<'a>(filename: &'a str, label: Option<&'a str>) -> &'a str {
+    match filename {
+        "-" => label.unwrap_or("(standard input)"),
+        _ => filename,
+    }
+}
+
+// Builds what goes in front of an output line, such as "file:12:340:" with
+// the line number and byte offset. Matched lines use ':' as the separator
+// and context lines '-', as in grep. With -Z the filename ends with a NUL
+// byte instead.
+fn format_prefix(
+    filename: Option<&str>,
+    line_num: Option<usize>,
+    byte_offset: Option<usize>,
+    separator: char,
+    null: bool,
+    color: bool,
+) -> String {
+    let mut prefix = String::new();
+    if let Some(filename) = filename {
+        if color {
+            prefix += &filename.magenta().to_string();
+        } else {
+            prefix += filename;
         }
+        prefix.push(if null { '\0' } else { separator });
     }
-    Ok(())
+    for number in [line_num, byte_offset].into_iter().flatten() {
+        if color {
+            prefix += &number.green().to_string();
+        } else {
+            prefix += &number.to_string();
+        }
+        prefix.push(separator);
+    }
+    prefix
+}
+
+// Matches are shown in bold red, like in GNU grep
+fn match_style() -> Style {
+    Style::new().red().bold()
+}
+
+// The line with every match in it highlighted
+fn highlight(pattern: &Regex, line: &str) -> String {
+    let mut highlighted = String::new();
+    let mut last = 0;
+    for m in pattern.find_iter(strip_terminator(line)) {
+        if !m.as_str().is_empty() {
+            highlighted += &line[last..m.start()];
+            highlighted += &m.as_str().style(match_style()).to_string();
+            last = m.end();
+        }
+    }
+    highlighted += &line[last..];
+    highlighted
 }
 
-fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
+// Files named outright are always searched. With smart, those found by
+// recursing leave out what ripgrep would: hidden and ignored files. With
+// follow, symbolic links to directories are followed too, and a file that
+// can be reached more than one way is only listed once.
+fn find_files(
+    paths: &[String],
+    recursive: bool,
+    smart: bool,
+    follow: bool,
+) -> Vec<MyResult<String>> {
     let mut results = vec![];
+    let mut seen = HashSet::new();
 
     for path in paths {
         match path.as_str() {
@@ -104,12 +854,20 @@ fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
                 Ok(metadata) => {
                     if metadata.is_dir() {
                         if recursive {
-                            for entry in WalkDir::new(path)
-                                .into_iter()
-                                .flatten()
-                                .filter(|e| e.file_type().is_file())
-                            {
-                                results.push(Ok(entry.path().display().to_string()));
+                            for entry in walk(path, smart, follow) {
+                                match entry {
+                                    // A loop of links, for one, is reported
+                                    // and the walk goes on
+                                    Err(e) => results.push(Err(From::from(e))),
+                                    Ok(file) => {
+                                        if follow
+                                            && file_id(&file).is_some_and(|id| !seen.insert(id))
+                                        {
+                                            continue;
+                                        }
+                                        results.push(Ok(file.display().to_string()));
+                                    }
+                                }
                             }
                         } else {
                             results.push(Err(From::from(format!("{} is a directory", path))));
@@ -120,7 +878,7 @@ fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
                 }
 
                 Err(e) => {
-                    results.push(Err(From::from(format!("{}: {}", path, e))));
+                    results.push(Err(From::from(error_message(path, &e))));
                 }
             },
         }
@@ -129,6 +887,73 @@ fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
     results
 }
 
+// The files under a directory, or why some could not be listed
+fn walk(path: &str, smart: bool, follow: bool) -> Vec<Result<PathBuf, String>> {
+    if smart {
+        WalkBuilder::new(path)
+            .require_git(false)
+            .follow_links(follow)
+            .build()
+            .filter_map(|entry| match entry {
+                Ok(e) if e.file_type().is_some_and(|t| t.is_file()) => Some(Ok(e.into_path())),
+                Ok(_) => None,
+                Err(e) => Some(Err(walk_error(ignore_error_path(&e), e.io_error(), &e))),
+            })
+            .collect()
+    } else {
+        WalkDir::new(path)
+            .follow_links(follow)
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Ok(e) if e.file_type().is_file() => Some(Ok(e.into_path())),
+                Ok(_) => None,
+                Err(e) => Some(Err(walk_error(e.path(), e.io_error(), &e))),
+            })
+            .collect()
+    }
+}
+
+// A walk error worded like one from opening the file it is about. Others,
+// such as a loop of links, keep the walker's own wording.
+fn walk_error(path: Option<&Path>, io_error: Option<&io::Error>, e: &dyn Error) -> String {
+    match (path, io_error) {
+        (Some(path), Some(io_error)) => {
+            // The ignore crate can wrap an error from walkdir in an io::Error
+            let io_error = io_error
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<walkdir::Error>())
+                .and_then(walkdir::Error::io_error)
+                .unwrap_or(io_error);
+            error_message(&path.display().to_string(), io_error)
+        }
+        _ => e.to_string(),
+    }
+}
+
+fn ignore_error_path(e: &ignore::Error) -> Option<&Path> {
+    match e {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            ignore_error_path(err)
+        }
+        _ => None,
+    }
+}
+
+// Tells files apart by device and inode, so that links to the same file
+// can be found
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(BufReader::new(io::stdin()))),
@@ -136,29 +961,325 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
+// Where the lines of a file come from: read one at a time, or with --mmap
+// from the whole file mapped into memory
+enum Source {
+    Stream(Box<dyn BufRead>),
+    Mapped(Mmap),
+}
+
+impl Source {
+    fn has_match(self, config: &Config) -> MyResult<bool> {
+        match (self, &config.mmap_pattern) {
+            (Source::Mapped(map), Some(bytes_pattern)) => {
+                Ok(mapped_lines(&map, &config.pattern, bytes_pattern)
+                    .next()
+                    .is_some())
+            }
+            (source, _) => has_match(
+                source.into_reader(),
+                &config.pattern,
+                config.invert_match,
+                config.terminator,
+            ),
+        }
+    }
+
+    fn count_lines(self, config: &Config) -> MyResult<usize> {
+        match (self, &config.mmap_pattern) {
+            (Source::Mapped(map), Some(bytes_pattern)) => {
+                let lines = mapped_lines(&map, &config.pattern, bytes_pattern);
+                Ok(lines.take(config.max_count.unwrap_or(usize::MAX)).count())
+            }
+            (source, _) => count_lines(
+                source.into_reader(),
+                &config.pattern,
+                config.invert_match,
+                config.terminator,
+                config.max_count,
+            ),
+        }
+    }
+
+    fn find_lines(self, config: &Config) -> MyResult<Vec<Line>> {
+        match (self, &config.mmap_pattern) {
+            (Source::Mapped(map), Some(bytes_pattern)) => {
+                Ok(mapped_lines(&map, &config.pattern, bytes_pattern)
+                    .take(config.max_count.unwrap_or(usize::MAX))
+                    .map(|(num, offset, line)| Line {
+                        num,
+                        offset,
+                        text: String::from_utf8_lossy(line).into_owned(),
+                        selected: true,
+                    })
+                    .collect())
+            }
+            (source, _) => find_lines(
+                source.into_reader(),
+                &config.pattern,
+                config.invert_match,
+                config.terminator,
+                config.max_count,
+                config.context.unwrap_or_default(),
+            ),
+        }
+    }
+
+    fn into_reader(self) -> Box<dyn BufRead> {
+        match self {
+            Source::Stream(file) => file,
+            Source::Mapped(map) => Box::new(Cursor::new(map)),
+        }
+    }
+}
+
+// Opens a file as a Source along with whether it is to be treated as
+// binary. Only regular files are mapped, and one that cannot be is read
+// as usual.
+fn open_source(filename: &str, config: &Config) -> MyResult<(Source, bool)> {
+    if let Some(map) = config
+        .mmap_pattern
+        .as_ref()
+        .and_then(|_| map_file(filename))
+    {
+        // The same bytes that a first read would give are checked
+        let binary = config.binary_files != BinaryFiles::Text
+            && map[..map.len().min(READ_BUF_SIZE)].contains(&0);
+        return Ok(match config.binary_files {
+            BinaryFiles::WithoutMatch if binary => (Source::Stream(Box::new(io::empty())), false),
+            // The bytes pattern can't see invalid UTF-8 the way the lossy
+            // text of each line does, so such a file is read line by line
+            _ if std::str::from_utf8(&map).is_err() => {
+                (Source::Stream(Box::new(Cursor::new(map))), binary)
+            }
+            _ => (Source::Mapped(map), binary),
+        });
+    }
+    let (file, binary) = open_input(filename, config.binary_files)?;
+    Ok((Source::Stream(file), binary))
+}
+
+// How much BufReader reads at first, which is what is checked for NUL bytes
+const READ_BUF_SIZE: usize = 8 * 1024;
+
+fn map_file(filename: &str) -> Option<Mmap> {
+    if filename == "-" {
+        return None;
+    }
+    let file = File::open(filename).ok()?;
+    if !file.metadata().ok()?.is_file() {
+        return None;
+    }
+    // SAFETY: the map is only read. As in other tools that map files, a
+    // file that is truncated while it is searched is not guarded against.
+    unsafe { Mmap::map(&file) }.ok()
+}
+
+// The lines of a mapped file that the pattern selects, with their 1-based
+// line numbers and byte offsets. The whole file is searched at once with
+// bytes_pattern, and the line around each match is checked again with
+// the line pattern, so that the result is the same as reading line by
+// line even for matches that run over a newline.
+fn mapped_lines<'a>(
+    buf: &'a [u8],
+    pattern: &'a Regex,
+    bytes_pattern: &'a bytes::Regex,
+) -> impl Iterator<Item = (usize, usize, &'a [u8])> + 'a {
+    let mut pos = 0;
+    let mut line_num = 1;
+    // Where newlines have been counted up to, for the line numbers
+    let mut counted = 0;
+
+    iter::from_fn(move || {
+        while pos < buf.len() {
+            let m = bytes_pattern.find_at(buf, pos)?;
+            let start = memrchr(b'\n', &buf[..m.start()]).map_or(0, |i| i + 1);
+            if start == buf.len() {
+                // An empty match after the last newline is not on a line
+                return None;
+            }
+            let end = memchr(b'\n', &buf[m.start()..]).map_or(buf.len(), |i| m.start() + i + 1);
+
+            line_num += memchr_iter(b'\n', &buf[counted..start]).count();
+            counted = start;
+            // A match may run past the line, so the search goes on from
+            // the next one whether or not this one is selected
+            pos = end;
+
+            let line = &buf[start..end];
+            if selects(pattern, &String::from_utf8_lossy(line), false) {
+                return Some((line_num, start, line));
+            }
+        }
+        None
+    })
+}
+
+// Opens a file along with whether it is to be treated as binary. With
+// --binary-files=without-match a binary file reads as empty.
+fn open_input(filename: &str, binary_files: BinaryFiles) -> MyResult<(Box<dyn BufRead>, bool)> {
+    let mut file = open(filename)?;
+    if binary_files == BinaryFiles::Text || !is_binary(&mut file)? {
+        return Ok((file, false));
+    }
+    match binary_files {
+        BinaryFiles::WithoutMatch => Ok((Box::new(io::empty()), false)),
+        _ => Ok((file, true)),
+    }
+}
+
+// A file is binary if there is a NUL byte in its first buffer, as in grep
+fn is_binary<T: BufRead>(file: &mut T) -> io::Result<bool> {
+    Ok(file.fill_buf()?.contains(&0))
+}
+
+// The terminator is left out so that $ can match at the end of the line
+fn selects(pattern: &Regex, line: &str, invert_match: bool) -> bool {
+    pattern.is_match(strip_terminator(line)) ^ invert_match
+}
+
+// Lines end with a newline, or a NUL byte with -z
+fn strip_terminator(line: &str) -> &str {
+    line.strip_suffix(['\n', '\0']).unwrap_or(line)
+}
+
+// The non-overlapping, non-empty matches in a line with where they start,
+// for -o
+fn matched_parts<'a>(
+    pattern: &'a Regex,
+    line: &'a str,
+    replace: Option<&'a str>,
+) -> impl Iterator<Item = (usize, Cow<'a, str>)> {
+    pattern
+        .captures_iter(strip_terminator(line))
+        .filter_map(move |caps| {
+            let m = caps.get(0).filter(|m| !m.as_str().is_empty())?;
+            // With --replace the part is the template filled in instead
+            let part = match replace {
+                Some(template) => {
+                    let mut text = String::new();
+                    caps.expand(template, &mut text);
+                    Cow::Owned(text)
+                }
+                None => Cow::Borrowed(m.as_str()),
+            };
+            Some((m.start(), part))
+        })
+}
+
+// The number of selected lines, up to max_count. Lines are only matched,
+// not kept, and valid UTF-8 is matched in place.
+fn count_lines<T: BufRead>(
+    mut file: T,
+    pattern: &Regex,
+    invert_match: bool,
+    terminator: u8,
+    max_count: Option<usize>,
+) -> MyResult<usize> {
+    let mut count = 0;
+    let mut line = vec![];
+
+    while max_count != Some(count) && file.read_until(terminator, &mut line)? > 0 {
+        if selects(pattern, &String::from_utf8_lossy(&line), invert_match) {
+            count += 1;
+        }
+        line.clear();
+    }
+
+    Ok(count)
+}
+
+// Whether any line matches, stopping at the first one that does
+fn has_match<T: BufRead>(
+    mut file: T,
+    pattern: &Regex,
+    invert_match: bool,
+    terminator: u8,
+) -> MyResult<bool> {
+    let mut line = vec![];
+
+    while file.read_until(terminator, &mut line)? > 0 {
+        if selects(pattern, &String::from_utf8_lossy(&line), invert_match) {
+            return Ok(true);
+        }
+        line.clear();
+    }
+
+    Ok(false)
+}
+
+// A line to print: one that is selected, or one shown as context
+#[derive(Debug, PartialEq)]
+struct Line {
+    // 1-based
+    num: usize,
+    // The byte offset at which the line starts
+    offset: usize,
+    text: String,
+    selected: bool,
+}
+
+// Returns the selected lines, up to max_count, with the given number of
+// context lines before and after each. A line is selected if it matches,
+// or with -v if it does not; context is whatever lines are around those.
 fn find_lines<T: BufRead>(
     mut file: T,
     pattern: &Regex,
     invert_match: bool,
-) -> MyResult<Vec<String>> {
-    let mut matches = vec![];
-    let mut line = String::new();
+    terminator: u8,
+    max_count: Option<usize>,
+    (before, after): (usize, usize),
+) -> MyResult<Vec<Line>> {
+    let mut lines = vec![];
+    // Lines that are shown if a selected line follows soon enough
+    let mut preceding = VecDeque::with_capacity(before);
+    let mut selected_count = 0;
+    // How many of the lines to come are context after a selected line
+    let mut following = 0;
+    let mut buf = vec![];
+    let mut offset = 0;
 
-    loop {
-        let bytes = file.read_line(&mut line)?;
+    for num in 1.. {
+        // After the last selected line there may still be context to show
+        let done = max_count == Some(selected_count);
+        if done && following == 0 {
+            break;
+        }
+        let bytes = file.read_until(terminator, &mut buf)?;
 
         if bytes == 0 {
             break;
         }
 
-        if pattern.is_match(&line) ^ invert_match {
-            matches.push(mem::take(&mut line));
+        // Invalid UTF-8 is replaced so that the line can still be matched
+        let text = String::from_utf8_lossy(&buf);
+        let selected = !done && selects(pattern, &text, invert_match);
+        let line = Line {
+            num,
+            offset,
+            text: text.into_owned(),
+            selected,
+        };
+        if selected {
+            selected_count += 1;
+            lines.extend(preceding.drain(..));
+            lines.push(line);
+            following = after;
+        } else if following > 0 {
+            following -= 1;
+            lines.push(line);
+        } else if before > 0 {
+            if preceding.len() == before {
+                preceding.pop_front();
+            }
+            preceding.push_back(line);
         }
+        offset += bytes;
 
-        line.clear();
+        buf.clear();
     }
 
-    Ok(matches)
+    Ok(lines)
 }
 
 // ---------------------------------------------------------
@@ -166,7 +1287,11 @@ fn find_lines<T: BufRead>(
 mod tests {
     use crate::find_lines;
 
-    use super::find_files;
+    use super::{
+        build_pattern, check_template, count_lines, display_name, find_files, format_prefix,
+        has_match, highlight, is_binary, mapped_lines, matched_parts, split_args, Args,
+    };
+    use clap::Parser;
     use rand::{distributions::Alphanumeric, Rng};
     use regex::{Regex, RegexBuilder};
     use std::io::Cursor;
@@ -174,19 +1299,19 @@ mod tests {
     #[test]
     fn test_find_files() {
         // Verify that the function finds a file known to exist
-        let files = find_files(&["./tests/inputs/fox.txt".to_string()], false);
+        let files = find_files(&["./tests/inputs/fox.txt".to_string()], false, false, false);
         assert_eq!(files.len(), 1);
         assert_eq!(files[0].as_ref().unwrap(), "./tests/inputs/fox.txt");
 
         // The function should reject a directory without the recursive option
-        let files = find_files(&["./tests/inputs".to_string()], false);
+        let files = find_files(&["./tests/inputs".to_string()], false, false, false);
         assert_eq!(files.len(), 1);
         if let Err(e) = &files[0] {
             assert_eq!(e.to_string(), "./tests/inputs is a directory");
         }
 
         // Verify the function recurses to find four files in the directory
-        let res = find_files(&["./tests/inputs".to_string()], true);
+        let res = find_files(&["./tests/inputs".to_string()], true, false, false);
         let mut files: Vec<String> = res
             .iter()
             .map(|r| r.as_ref().unwrap().replace("\\", "/"))
@@ -202,6 +1327,15 @@ mod tests {
                 "./tests/inputs/nobody.txt",
             ]
         );
+
+        // Smart recursion skips hidden and ignored files, not named ones
+        let res = find_files(&["./tests/smart".to_string()], true, true, false);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].as_ref().unwrap(), "./tests/smart/keep.txt");
+        let res = find_files(&["./tests/smart/skip.log".to_string()], true, true, false);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].as_ref().unwrap(), "./tests/smart/skip.log");
+
         // Generate a random string to represent a nonexistent file
         let bad: String = rand::thread_rng()
             .sample_iter(&Alphanumeric)
@@ -209,7 +1343,7 @@ mod tests {
             .map(char::from)
             .collect();
         // Verify that the function returns the bad file as an error
-        let files = find_files(&[bad], false);
+        let files = find_files(&[bad], false, false, false);
         assert_eq!(files.len(), 1);
         assert!(files[0].is_err());
     }
@@ -220,15 +1354,24 @@ mod tests {
 
         // The pattern _or_ should match the one line, "Lorem"
         let re1 = Regex::new("or").unwrap();
-        let matches = find_lines(Cursor::new(&text), &re1, false);
+        let matches = find_lines(Cursor::new(&text), &re1, false, b'\n', None, (0, 0));
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 1);
 
         // When inverted, the function should match the other two lines
-        let matches = find_lines(Cursor::new(&text), &re1, true);
+        let matches = find_lines(Cursor::new(&text), &re1, true, b'\n', None, (0, 0));
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 2);
 
+        // Offsets count every byte before the line, \r included
+        let matches = find_lines(Cursor::new(&text), &re1, true, b'\n', None, (0, 0)).unwrap();
+        let offsets: Vec<_> = matches.iter().map(|line| (line.num, line.offset)).collect();
+        assert_eq!(offsets, [(2, 6), (3, 13)]);
+
+        // Reading stops after max_count lines are selected
+        let matches = find_lines(Cursor::new(&text), &re1, true, b'\n', Some(1), (0, 0));
+        assert_eq!(matches.unwrap().len(), 1);
+
         // This regex will be case-insensitive
         let re2 = RegexBuilder::new("or")
             .case_insensitive(true)
@@ -236,13 +1379,283 @@ mod tests {
             .unwrap();
 
         // The two lines "Lorem" and "DOLOR" should match
-        let matches = find_lines(Cursor::new(&text), &re2, false);
+        let matches = find_lines(Cursor::new(&text), &re2, false, b'\n', None, (0, 0));
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 2);
 
         // When inverted, the one remaining line should match
-        let matches = find_lines(Cursor::new(&text), &re2, true);
+        let matches = find_lines(Cursor::new(&text), &re2, true, b'\n', None, (0, 0));
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 1);
+
+        // Context is given as unselected lines around the selected ones
+        let text = b"a\nb\nc\nd\ne\nf\n";
+        let re = Regex::new("[bf]").unwrap();
+        let lines = |invert_match, max_count, context| {
+            find_lines(
+                Cursor::new(&text),
+                &re,
+                invert_match,
+                b'\n',
+                max_count,
+                context,
+            )
+            .unwrap()
+            .iter()
+            .map(|line| (line.num, line.selected))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            lines(false, None, (1, 1)),
+            [(1, false), (2, true), (3, false), (5, false), (6, true)]
+        );
+        assert_eq!(
+            lines(false, None, (0, 2)),
+            [(2, true), (3, false), (4, false), (6, true)]
+        );
+        // With -v the lines that match are the context
+        assert_eq!(
+            lines(true, Some(2), (0, 1)),
+            [(1, true), (2, false), (3, true), (4, false)]
+        );
+    }
+
+    #[test]
+    fn test_split_args() {
+        let split = |args: &[&str]| {
+            let args = Args::try_parse_from([&["grepr"], args].concat()).unwrap();
+            split_args(&args).unwrap()
+        };
+
+        assert_eq!(split(&["fox"]), (vec!["fox".into()], vec!["-".into()]));
+        assert_eq!(
+            split(&["fox", "a.txt"]),
+            (vec!["fox".into()], vec!["a.txt".into()])
+        );
+        // With -e the first positional argument is a file
+        assert_eq!(
+            split(&["-e", "fox", "-e", "-dog", "a.txt", "b.txt"]),
+            (
+                vec!["fox".into(), "-dog".into()],
+                vec!["a.txt".into(), "b.txt".into()]
+            )
+        );
+        assert_eq!(
+            split(&["-e", "fox"]),
+            (vec!["fox".into()], vec!["-".into()])
+        );
+        assert!(Args::try_parse_from(["grepr"]).is_err());
+    }
+
+    #[test]
+    fn test_build_pattern() {
+        let pattern = |args: &[&str]| {
+            let args = Args::try_parse_from([&["grepr"], args].concat()).unwrap();
+            let (patterns, _) = split_args(&args).unwrap();
+            build_pattern(&patterns, &args).unwrap()
+        };
+
+        // -w only matches the pattern as a whole word
+        let re = pattern(&["-w", "the"]);
+        assert!(re.is_match("over the lazy dog"));
+        assert!(!re.is_match("other"));
+        assert!(!re.is_match("another"));
+
+        // Each alternative is anchored, not just the first and last
+        let re = pattern(&["-w", "cat|dog"]);
+        assert!(re.is_match("a dog."));
+        assert!(!re.is_match("dogma"));
+        let re = pattern(&["-x", "cat|dog"]);
+        assert!(re.is_match("cat"));
+        assert!(!re.is_match("cats and dog"));
+        assert!(!re.is_match("a dog"));
+
+        // Case-insensitivity still applies
+        let re = pattern(&["-wi", "THE"]);
+        assert!(re.is_match("The fox"));
+        assert!(!re.is_match("Then"));
+        let re = pattern(&["-xi", "the fox"]);
+        assert!(re.is_match("The Fox"));
+
+        assert!(Args::try_parse_from(["grepr", "-w", "-x", "the"]).is_err());
+
+        // -F matches regex syntax literally, also with -i, -w and -x
+        let re = pattern(&["-F", "a.b(c)*\\"]);
+        assert!(re.is_match("x a.b(c)*\\ y"));
+        assert!(!re.is_match("axb(c)\\"));
+        let re = pattern(&["-Fi", "A.B"]);
+        assert!(re.is_match("a.b"));
+        assert!(!re.is_match("axb"));
+        let re = pattern(&["-Fx", "a|b"]);
+        assert!(re.is_match("a|b"));
+        assert!(!re.is_match("a"));
+    }
+
+    #[test]
+    fn test_matched_parts() {
+        let re = Regex::new("o+").unwrap();
+        let parts: Vec<_> = matched_parts(&re, "foo boo zo\n", None).collect();
+        assert_eq!(parts, [(1, "oo".into()), (5, "oo".into()), (9, "o".into())]);
+
+        // Empty matches are skipped
+        let re = Regex::new("x*").unwrap();
+        assert_eq!(
+            matched_parts(&re, "axxb\n", None).collect::<Vec<_>>(),
+            [(1, "xx".into())]
+        );
+        assert_eq!(matched_parts(&re, "ab", None).count(), 0);
+
+        // With a template the groups are filled in
+        let re = Regex::new(r"(?<key>\w+)=(\d+)").unwrap();
+        let parts: Vec<_> = matched_parts(&re, "a=1 b=22\n", Some("$2:${key}$$")).collect();
+        assert_eq!(parts, [(0, "1:a$".into()), (4, "22:b$".into())]);
+    }
+
+    #[test]
+    fn test_check_template() {
+        let re = Regex::new(r"(?<key>\w+)=(\d+)").unwrap();
+        for template in [
+            "", "$0 $1 $2", "${key}", "$key-$1", "$$3", "cost: $", "${2}x",
+        ] {
+            assert!(check_template(template, &re).is_ok(), "{}", template);
+        }
+        for template in ["$3", "$value", "${1}${other}", "$1x"] {
+            assert!(check_template(template, &re).is_err(), "{}", template);
+        }
+        let e = check_template("$3", &re).unwrap_err();
+        assert_eq!(e.to_string(), "Invalid replacement \"$3\": no group 3");
+    }
+
+    #[test]
+    fn test_highlight() {
+        let re = Regex::new("o+").unwrap();
+        assert_eq!(
+            highlight(&re, "foo bar zo\n"),
+            "f\x1b[31;1moo\x1b[0m bar z\x1b[31;1mo\x1b[0m\n"
+        );
+        assert_eq!(highlight(&re, "bar\n"), "bar\n");
+
+        let re = Regex::new("x*").unwrap();
+        assert_eq!(highlight(&re, "axb"), "a\x1b[31;1mx\x1b[0mb");
+    }
+
+    #[test]
+    fn test_count_lines() {
+        let text = b"Loren\nIpsum\r\nDOLOR";
+        let re = Regex::new("or").unwrap();
+        assert_eq!(
+            count_lines(Cursor::new(&text), &re, false, b'\n', None).unwrap(),
+            1
+        );
+        assert_eq!(
+            count_lines(Cursor::new(&text), &re, true, b'\n', None).unwrap(),
+            2
+        );
+        assert_eq!(
+            count_lines(Cursor::new(&text), &re, true, b'\n', Some(1)).unwrap(),
+            1
+        );
+        assert_eq!(
+            count_lines(Cursor::new(b""), &re, true, b'\n', None).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_mapped_lines() {
+        let lines = |pattern: &str, text: &'static [u8]| {
+            let re = Regex::new(pattern).unwrap();
+            let bytes_re = regex::bytes::RegexBuilder::new(pattern)
+                .multi_line(true)
+                .build()
+                .unwrap();
+            mapped_lines(text, &re, &bytes_re)
+                .map(|(num, offset, line)| (num, offset, line.to_vec()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            lines("or", b"Loren\nIpsum\r\nDOLOR\nor"),
+            [(1, 0, b"Loren\n".to_vec()), (4, 19, b"or".to_vec())]
+        );
+        // The first match runs over two lines, and the second line is
+        // found all the same
+        assert_eq!(lines("x[^y]*z", b"x\nxz\nab\n"), [(2, 2, b"xz\n".to_vec())]);
+        // No line follows the last newline
+        assert_eq!(lines("$", b"a\n\n").len(), 2);
+        assert!(lines("", b"").is_empty());
+    }
+
+    #[test]
+    fn test_has_match() {
+        let text = b"Loren\nIpsum\r\nDOLOR";
+        let re = Regex::new("or").unwrap();
+        assert!(has_match(Cursor::new(&text), &re, false, b'\n').unwrap());
+        assert!(has_match(Cursor::new(&text), &re, true, b'\n').unwrap());
+
+        let re = Regex::new("nope").unwrap();
+        assert!(!has_match(Cursor::new(&text), &re, false, b'\n').unwrap());
+        assert!(!has_match(Cursor::new(b""), &re, true, b'\n').unwrap());
+
+        // With -z lines end with NUL, so a newline is just another byte
+        let text = b"one\ntwo\0three";
+        let re = Regex::new("^two$").unwrap();
+        assert!(!has_match(Cursor::new(&text), &re, false, b'\n').unwrap());
+        assert!(!has_match(Cursor::new(&text), &re, false, b'\0').unwrap());
+        let re = Regex::new("(?m)^two$").unwrap();
+        assert!(has_match(Cursor::new(&text), &re, false, b'\0').unwrap());
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(&mut Cursor::new(b"")).unwrap());
+        assert!(!is_binary(&mut Cursor::new(b"plain\ntext\n")).unwrap());
+        assert!(is_binary(&mut Cursor::new(b"bin\0ary\n")).unwrap());
+
+        // Only peeks, so the whole file can still be read afterwards
+        let mut file = Cursor::new(b"a\0b");
+        assert!(is_binary(&mut file).unwrap());
+        assert_eq!(file.position(), 0);
+    }
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(display_name("-", None), "(standard input)");
+        assert_eq!(display_name("-", Some("pipe")), "pipe");
+        assert_eq!(display_name("a.txt", None), "a.txt");
+        assert_eq!(display_name("a.txt", Some("pipe")), "a.txt");
+    }
+
+    #[test]
+    fn test_format_prefix() {
+        assert_eq!(format_prefix(None, None, None, ':', false, false), "");
+        assert_eq!(
+            format_prefix(Some("a.txt"), None, None, ':', false, false),
+            "a.txt:"
+        );
+        assert_eq!(
+            format_prefix(None, Some(12), None, ':', false, false),
+            "12:"
+        );
+        assert_eq!(
+            format_prefix(Some("a.txt"), Some(12), None, '-', false, false),
+            "a.txt-12-"
+        );
+        assert_eq!(
+            format_prefix(Some("a.txt"), Some(12), None, ':', false, true),
+            "\x1b[35ma.txt\x1b[39m:\x1b[32m12\x1b[39m:"
+        );
+        assert_eq!(
+            format_prefix(Some("a.txt"), Some(12), None, ':', true, false),
+            "a.txt\x0012:"
+        );
+        assert_eq!(
+            format_prefix(Some("a.txt"), Some(12), Some(340), ':', false, false),
+            "a.txt:12:340:"
+        );
+        assert_eq!(
+            format_prefix(None, None, Some(0), ':', false, true),
+            "\x1b[32m0\x1b[39m:"
+        );
     }
 }
@@ -1,6 +1,9 @@
 fn main() {
-    if let Err(e) = grepr::get_args().and_then(grepr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match grepr::get_args().and_then(grepr::run) {
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        Ok(status) => std::process::exit(status.code()),
     }
 }
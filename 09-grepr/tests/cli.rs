@@ -1,7 +1,12 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{fs, path::Path};
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command as StdCommand, Stdio},
+};
 use sys_info::os_type;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
@@ -11,7 +16,10 @@ const BUSTLE: &str = "tests/inputs/bustle.txt";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const NOBODY: &str = "tests/inputs/nobody.txt";
+const LATIN1: &str = "tests/encoding/latin1.txt";
 const INPUTS_DIR: &str = "tests/inputs";
+const PATTERNS: &str = "tests/patterns/fox_nobody.txt";
+const BINARY: &str = "tests/binary/needle.bin";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -42,9 +50,9 @@ fn dies_no_args() -> TestResult {
 #[test]
 fn dies_bad_pattern() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&["*foo", FOX])
+        .args(["*foo", FOX])
         .assert()
-        .failure()
+        .code(2)
         .stderr(predicate::str::contains("Invalid pattern \"*foo\""));
     Ok(())
 }
@@ -53,11 +61,12 @@ fn dies_bad_pattern() -> TestResult {
 #[test]
 fn warns_bad_file() -> TestResult {
     let bad = gen_bad_file();
-    let expected = format!("{}: .* [(]os error 2[)]", bad);
+    let expected = format!("grepr: {}: No such file or directory\n", bad);
     Command::cargo_bin(PRG)?
-        .args(&["foo", &bad])
+        .args(["foo", &bad])
         .assert()
-        .stderr(predicate::str::is_match(expected)?);
+        .code(2)
+        .stderr(expected);
     Ok(())
 }
 
@@ -72,7 +81,7 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
         expected_file
     };
 
-    let expected = fs::read_to_string(&expected_file)?;
+    let expected = fs::read_to_string(expected_file)?;
 
     Command::cargo_bin(PRG)?
         .args(args)
@@ -168,6 +177,1174 @@ fn recursive_insensitive() -> TestResult {
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn line_number() -> TestResult {
+    run(
+        &["-n", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized.line_number",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_number_multiple_files() -> TestResult {
+    run(
+        &["--line-number", "The", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.capitalized.line_number",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_number_ignored_with_count() -> TestResult {
+    run(
+        &["-cn", "The", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.capitalized.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn line_number_inverted() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-nv", "e", BUSTLE])
+        .assert()
+        .success()
+        .stdout("5:\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "us", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!("{}\n{}\n", BUSTLE, NOBODY));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_without_match() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--files-without-match", "us", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!("{}\n{}\n", EMPTY, FOX));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches_inverted() -> TestResult {
+    // With -v a file is listed when any of its lines lacks the pattern
+    Command::cargo_bin(PRG)?
+        .args(["-lv", "us", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!("{}\n{}\n{}\n", BUSTLE, FOX, NOBODY));
+    Command::cargo_bin(PRG)?
+        .args(["-Lv", "us", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", EMPTY));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "us"])
+        .write_stdin(fs::read_to_string(NOBODY)?)
+        .assert()
+        .success()
+        .stdout("(standard input)\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_match_exits_1() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "zebra", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .code(1)
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["zebra", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exit_codes() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["fox", FOX])
+        .assert()
+        .code(0);
+    Command::cargo_bin(PRG)?
+        .args(["zebra", FOX])
+        .assert()
+        .code(1);
+    Command::cargo_bin(PRG)?
+        .args(["fox", &bad])
+        .assert()
+        .code(2);
+    // A missing file is an error even when another file matches
+    Command::cargo_bin(PRG)?
+        .args(["fox", FOX, &bad])
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains("fox"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-q", "fox", FOX])
+        .assert()
+        .code(0)
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["--quiet", "-c", "zebra", FOX, BUSTLE])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet_exits_at_first_match() -> TestResult {
+    // The missing file is never reached, so it is not an error
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-q", "fox", FOX, &bad])
+        .assert()
+        .code(0)
+        .stdout("")
+        .stderr("");
+    // Matches after a missing file still make the status 0
+    Command::cargo_bin(PRG)?
+        .args(["--silent", "fox", &bad, FOX])
+        .assert()
+        .code(0)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_file_matches() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["needle", BINARY])
+        .assert()
+        .success()
+        .stdout(format!("Binary file {} matches\n", BINARY));
+    Command::cargo_bin(PRG)?
+        .args(["zebra", BINARY])
+        .assert()
+        .code(1)
+        .stdout("");
+    // Counts are still given for binary files
+    Command::cargo_bin(PRG)?
+        .args(["-c", "hay", BINARY])
+        .assert()
+        .success()
+        .stdout("2\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_files_text() -> TestResult {
+    for flag in ["-a", "--text", "--binary-files=text"] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "needle", BINARY])
+            .assert()
+            .success()
+            .stdout("the needle\n");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_files_without_match() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--binary-files=without-match", "needle", BINARY, FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["--binary-files=without-match", "-L", "needle", BINARY])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", BINARY));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn with_filename() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-H", "fox", FOX])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:The quick brown fox jumps over the lazy dog.\n",
+            FOX
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["--with-filename", "-n", "fox", FOX])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:1:The quick brown fox jumps over the lazy dog.\n",
+            FOX
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["-Hc", "fox", FOX])
+        .assert()
+        .success()
+        .stdout(format!("{}:1\n", FOX));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_filename() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-h", "-n", "fox", FOX, BUSTLE])
+        .assert()
+        .success()
+        .stdout("1:The quick brown fox jumps over the lazy dog.\n");
+    Command::cargo_bin(PRG)?
+        .args(["--no-filename", "-c", "fox", FOX, BUSTLE])
+        .assert()
+        .success()
+        .stdout("1\n0\n");
+    // Listing files is not affected
+    Command::cargo_bin(PRG)?
+        .args(["-hl", "fox", FOX, BUSTLE])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", FOX));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_with_and_no_filename() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-H", "-h", "fox", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn help() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--no-filename"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn null_filenames() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-lZ", "fox|Nobody", BUSTLE, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!("{}\0{}\0", FOX, NOBODY));
+    Command::cargo_bin(PRG)?
+        .args(["--null", "-n", "fox", FOX, BUSTLE])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\x001:The quick brown fox jumps over the lazy dog.\n",
+            FOX
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["-Zc", "fox", FOX, BUSTLE])
+        .assert()
+        .success()
+        .stdout(format!("{}\x001\n{}\x000\n", FOX, BUSTLE));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn null_filenames_xargs() -> TestResult {
+    let output = Command::cargo_bin(PRG)?
+        .args(["-lZ", "fox", FOX, BUSTLE])
+        .output()?;
+    let mut xargs = StdCommand::new("xargs")
+        .args(["-0", "cat"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    xargs.stdin.take().unwrap().write_all(&output.stdout)?;
+    let cat = xargs.wait_with_output()?;
+    assert!(cat.status.success());
+    assert_eq!(String::from_utf8(cat.stdout)?, fs::read_to_string(FOX)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn null_data() -> TestResult {
+    let input = "first\nrecord\0second\nrecord\0the fox\nagain";
+    run_stdin(&["-z", "fox"], input, "the fox\nagain")?;
+    run_stdin(&["--null-data", "-c", "record"], input, "2\n")?;
+    run_stdin(&["-zo", "sec.nd|fox"], input, "second\0fox\0")?;
+    // A record with a NUL byte is not binary with -z
+    run_stdin(
+        &["-z", "(?m)^record$"],
+        input,
+        "first\nrecord\0second\nrecord\0",
+    )?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_match_serial() -> TestResult {
+    for args in [
+        &["-r", "-n", "the", INPUTS_DIR][..],
+        &["-ric", "the", INPUTS_DIR],
+        &["-rl", "-v", "fox", INPUTS_DIR, FOX],
+    ] {
+        let serial = Command::cargo_bin(PRG)?.args(args).output()?;
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .args(["--threads", "4"])
+            .assert()
+            .code(serial.status.code().unwrap())
+            .stdout(serial.stdout);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_unordered() -> TestResult {
+    let serial = Command::cargo_bin(PRG)?
+        .args(["-ri", "the", INPUTS_DIR])
+        .output()?;
+    let output = Command::cargo_bin(PRG)?
+        .args(["-ri", "the", INPUTS_DIR, "--threads=4", "--unordered"])
+        .output()?;
+    assert!(output.status.success());
+
+    // The same lines, though the files may come in any order
+    let sorted = |stdout: Vec<u8>| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut lines: Vec<_> =
+            String::from_utf8(stdout)?.lines().map(String::from).collect();
+        lines.sort();
+        Ok(lines)
+    };
+    assert_eq!(sorted(output.stdout)?, sorted(serial.stdout)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn threads_errors() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "4", "fox", FOX, &bad, BUSTLE])
+        .assert()
+        .code(2)
+        .stdout(format!(
+            "{}:The quick brown fox jumps over the lazy dog.\n",
+            FOX
+        ))
+        .stderr(predicate::str::contains(bad));
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "4", "-q", "fox", BUSTLE, FOX, NOBODY])
+        .assert()
+        .code(0)
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["--threads", "0", "fox", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--threads"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn smart_recursion() -> TestResult {
+    let output = Command::cargo_bin(PRG)?
+        .args(["-rl", "needle", "tests/smart"])
+        .output()?;
+    let mut files: Vec<_> = String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| line.replace('\\', "/"))
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            "tests/smart/.hidden.txt",
+            "tests/smart/keep.txt",
+            "tests/smart/skip.log",
+        ]
+    );
+
+    Command::cargo_bin(PRG)?
+        .args(["-rl", "--smart", "needle", "tests/smart"])
+        .assert()
+        .success()
+        .stdout(format!("tests{0}smart{0}keep.txt\n", std::path::MAIN_SEPARATOR));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn smart_named_files() -> TestResult {
+    // Files given outright are searched even if ignored or hidden
+    Command::cargo_bin(PRG)?
+        .args([
+            "-r",
+            "--smart",
+            "-c",
+            "needle",
+            "tests/smart/skip.log",
+            "tests/smart/.hidden.txt",
+        ])
+        .assert()
+        .success()
+        .stdout("tests/smart/skip.log:1\ntests/smart/.hidden.txt:1\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn byte_offset() -> TestResult {
+    // The lines of nobody.txt end with \r\n, which counts as two bytes
+    Command::cargo_bin(PRG)?
+        .args(["-b", "Nobody", NOBODY])
+        .assert()
+        .success()
+        .stdout(
+            "0:I'm Nobody! Who are you?\r\n\
+             26:Are you\u{2014}Nobody\u{2014}too?\r\n",
+        );
+    Command::cargo_bin(PRG)?
+        .args(["-Hnb", "fox", FOX])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:1:0:The quick brown fox jumps over the lazy dog.\n",
+            FOX
+        ));
+    run_stdin(&["--byte-offset", "fox"], "one\ntwo fox\n", "4:two fox\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn byte_offset_only_matching() -> TestResult {
+    // With -o the offset is that of the match, not the line
+    Command::cargo_bin(PRG)?
+        .args(["-Hnbo", "-i", "nobody", NOBODY])
+        .assert()
+        .success()
+        .stdout(format!("{0}:1:4:Nobody\n{0}:2:36:Nobody\n", NOBODY));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_messages() -> TestResult {
+    let bad = gen_bad_file();
+    for flag in ["-s", "--no-messages"] {
+        Command::cargo_bin(PRG)?
+            .args([flag, "fox", &bad, FOX])
+            .assert()
+            .code(2)
+            .stdout(format!(
+                "{}:The quick brown fox jumps over the lazy dog.\n",
+                FOX
+            ))
+            .stderr("");
+    }
+    // Errors are still reported for each file without -s, once each
+    Command::cargo_bin(PRG)?
+        .args(["-r", "fox", &bad, INPUTS_DIR, &bad])
+        .assert()
+        .code(2)
+        .stderr(format!(
+            "grepr: {0}: No such file or directory\n\
+             grepr: {0}: No such file or directory\n",
+            bad
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_per_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "the", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:1\n{}:0\n{}:1\n{}:3\n",
+            BUSTLE, EMPTY, FOX, NOBODY
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["-vc", "the", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:8\n{}:0\n{}:0\n{}:6\n",
+            BUSTLE, EMPTY, FOX, NOBODY
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-cm", "1", "-i", "the", BUSTLE, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!("{}:1\n{}:1\n", BUSTLE, NOBODY));
+    Command::cargo_bin(PRG)?
+        .args(["--max-count=2", "-n", "-i", "the", BUSTLE])
+        .assert()
+        .success()
+        .stdout("1:The bustle in a house\n2:The morning after death\n");
+    // Nothing is read with -m 0, so nothing matches
+    Command::cargo_bin(PRG)?
+        .args(["-m", "0", "-c", "fox", FOX])
+        .assert()
+        .code(1)
+        .stdout("0\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_total() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--total", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}:3\n{}:0\n{}:1\n{}:3\ntotal:7\n",
+            BUSTLE, EMPTY, FOX, NOBODY
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["-vc", "--total", "the", FOX])
+        .assert()
+        .code(1)
+        .stdout("0\ntotal:0\n");
+    Command::cargo_bin(PRG)?
+        .args(["--total", "the", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--count"));
+    Ok(())
+}
+
+// --------------------------------------------------
+// Runs the args on each file with and without --mmap
+fn assert_mmap_same(args: &[&str], files: &[&str]) -> TestResult {
+    for file in files {
+        let streamed = Command::cargo_bin(PRG)?.args(args).arg(file).output()?;
+        Command::cargo_bin(PRG)?
+            .arg("--mmap")
+            .args(args)
+            .arg(file)
+            .assert()
+            .code(streamed.status.code().unwrap())
+            .stdout(streamed.stdout);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mmap_matches_streaming() -> TestResult {
+    let files = [BUSTLE, EMPTY, FOX, NOBODY, BINARY, LATIN1];
+    for args in [
+        &["-n", "a.b"][..],
+        &["-n", "the"],
+        &["-ci", "the"],
+        &["-nbo", "-i", "nobody"],
+        &["-n", ""],
+        &["-x", ""],
+        &["-nw", "the"],
+        &["-li", "the"],
+        &["-n", "^"],
+        &["-n", "$"],
+        &["-n", r"\s"],
+        &["-b", "-i", r"o\s*t"],
+        &["-m", "2", "-n", "."],
+        &["-c", "needle"],
+        &["-a", "needle"],
+        &["-nv", "the"],
+    ] {
+        assert_mmap_same(args, &files)?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mmap_large_file() -> TestResult {
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect();
+    let path = std::env::temp_dir().join(format!("grepr-mmap-{}.txt", suffix));
+
+    // About 50MB, with a needle every so often
+    let mut text = String::with_capacity(50 << 20);
+    let mut num = 0;
+    while text.len() < 50 << 20 {
+        num += 1;
+        if num % 10_007 == 0 {
+            text += &format!("line {} has a Needle in it\n", num);
+        } else {
+            text += &format!("line {} is only hay, hay and more hay\n", num);
+        }
+    }
+    fs::write(&path, text)?;
+
+    let file = path.to_str().unwrap();
+    let result = (|| {
+        assert_mmap_same(&["-n", "Needle"], &[file])?;
+        assert_mmap_same(&["-ci", "needle"], &[file])?;
+        assert_mmap_same(&["-b", "-m", "5", "Needle"], &[file])
+    })();
+    fs::remove_file(&path)?;
+    result
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_name() -> TestResult {
+    let input = fs::read_to_string(FOX)?;
+    let expected = |name: &str| {
+        format!(
+            "{0}:1:The quick brown fox jumps over the lazy dog.\n\
+             {1}:1:The quick brown fox jumps over the lazy dog.\n",
+            name, FOX
+        )
+    };
+    Command::cargo_bin(PRG)?
+        .args(["-n", "fox", "-", FOX])
+        .write_stdin(input.clone())
+        .assert()
+        .success()
+        .stdout(expected("(standard input)"));
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--label", "pipe", "fox", "-", FOX])
+        .write_stdin(input.clone())
+        .assert()
+        .success()
+        .stdout(expected("pipe"));
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--label=pipe", "fox", "-", FOX])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(format!("pipe\n{}\n", FOX));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn context() -> TestResult {
+    let input = "a1\nb\na2\na3\na4\nc\n";
+    run_stdin(
+        &["-n", "-C", "1", "b|c"],
+        input,
+        "1-a1\n2:b\n3-a2\n--\n5-a4\n6:c\n",
+    )?;
+    run_stdin(&["-A", "1", "b"], input, "b\na2\n")?;
+    run_stdin(&["--before-context=2", "c"], input, "a3\na4\nc\n")?;
+    // Groups are set apart even with no context lines
+    run_stdin(&["-C", "0", "b|c"], input, "b\n--\nc\n")?;
+
+    // Also across files
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-A", "1", "-i", "the", FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{0}:1:The quick brown fox jumps over the lazy dog.\n\
+             --\n\
+             {1}:3:Then there's a pair of us!\r\n\
+             {1}:4:Don't tell! they'd advertise\u{2014}you know!\r\n\
+             {1}-5-\r\n\
+             --\n\
+             {1}:8:To tell one's name\u{2014}the livelong June\u{2014}\r\n\
+             {1}-9-To an admiring Bog!\r\n",
+            FOX, NOBODY
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn invert_match_matrix() -> TestResult {
+    // With -v the selected lines are b and c, and the others are context
+    let input = "a1\nb\na2\na3\na4\nc\n";
+    run_stdin(&["-vc", "a"], input, "2\n")?;
+    run_stdin(
+        &["-v", "-n", "-C", "1", "a"],
+        input,
+        "1-a1\n2:b\n3-a2\n--\n5-a4\n6:c\n",
+    )?;
+    run_stdin(&["-vl", "a"], input, "(standard input)\n")?;
+    run_stdin(&["-v", "-m", "1", "a"], input, "b\n")?;
+    run_stdin(&["-v", "-m", "1", "-A", "1", "a"], input, "b\na2\n")?;
+    run_stdin(&["-vc", "-m", "1", "a"], input, "1\n")?;
+    // Selected lines have no matched parts, so -o prints nothing, but the
+    // lines were still selected
+    Command::cargo_bin(PRG)?
+        .args(["-vo", "a"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["-vo", "-C", "1", "a"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_links() -> TestResult {
+    use std::os::unix::fs::symlink;
+
+    // root/dir/file.txt can also be reached as root/link/file.txt, and
+    // root/dir/back leads back to root
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect();
+    let root = std::env::temp_dir().join(format!("grepr-follow-{}", suffix));
+    fs::create_dir_all(root.join("dir"))?;
+    fs::write(root.join("dir/file.txt"), "a needle\n")?;
+    symlink(root.join("dir"), root.join("link"))?;
+    symlink(&root, root.join("dir/back"))?;
+    let root_name = root.to_str().unwrap();
+    let expected = format!("{}/dir/file.txt:1\n", root_name);
+
+    let result = (|| -> TestResult {
+        // Links are not followed by default
+        Command::cargo_bin(PRG)?
+            .args(["-rcH", "needle", root_name])
+            .assert()
+            .success()
+            .stdout(expected.clone())
+            .stderr("");
+
+        for smart in [&[][..], &["--smart"]] {
+            let output = Command::cargo_bin(PRG)?
+                .args(["-rcH", "--follow", "needle", root_name])
+                .args(smart)
+                .output()?;
+            // The loop is reported, and the file is searched only once
+            // whichever way it was reached first
+            assert_eq!(output.status.code(), Some(2));
+            let stdout = String::from_utf8(output.stdout)?;
+            assert_eq!(stdout.lines().count(), 1);
+            assert!(stdout.ends_with("/file.txt:1\n"));
+            assert!(!output.stderr.is_empty());
+        }
+        Ok(())
+    })();
+    fs::remove_dir_all(&root)?;
+    result
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn walk_errors_like_open_errors() -> TestResult {
+    use std::os::unix::fs::symlink;
+
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(7)
+        .map(char::from)
+        .collect();
+    let root = std::env::temp_dir().join(format!("grepr-walk-{}", suffix));
+    fs::create_dir_all(&root)?;
+    symlink("nowhere", root.join("dangling"))?;
+    let root_name = root.to_str().unwrap();
+
+    let result = (|| -> TestResult {
+        for smart in [&[][..], &["--smart"]] {
+            Command::cargo_bin(PRG)?
+                .args(["-r", "--follow", "needle", root_name])
+                .args(smart)
+                .assert()
+                .code(2)
+                .stderr(format!(
+                    "grepr: {}/dangling: No such file or directory\n",
+                    root_name
+                ));
+        }
+        Ok(())
+    })();
+    fs::remove_dir_all(&root)?;
+    result
+}
+
+// --------------------------------------------------
+#[test]
+fn replace() -> TestResult {
+    let input = "version=12 and version=3\nnothing\nname=x version=45\n";
+    run_stdin(
+        &["--replace", "$1", "-o", r"version=(\d+)"],
+        input,
+        "12\n3\n45\n",
+    )?;
+    run_stdin(
+        &["-o", "--replace", "${key} is $value", r"(?<key>\w+)=(?<value>\w+)"],
+        input,
+        "version is 12\nversion is 3\nname is x\nversion is 45\n",
+    )?;
+    // $$ is a literal $
+    run_stdin(
+        &["-o", "--replace=$$$1.00", r"version=(\d+)"],
+        input,
+        "$12.00\n$3.00\n$45.00\n",
+    )?;
+    // Prefixes come before the replacement as they do before matches
+    run_stdin(
+        &["-noH", "--label", "in", "--replace", "v$1", r"version=(\d+)"],
+        input,
+        "in:1:v12\nin:1:v3\nin:3:v45\n",
+    )?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_replace() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-o", "--replace", "$2", r"version=(\d+)", FOX])
+        .assert()
+        .code(2)
+        .stderr("Invalid replacement \"$2\": no group 2\n");
+    Command::cargo_bin(PRG)?
+        .args(["-o", "--replace", "${name}", r"version=(\d+)", FOX])
+        .assert()
+        .code(2)
+        .stderr("Invalid replacement \"${name}\": no group name\n");
+    Command::cargo_bin(PRG)?
+        .args(["--replace", "$1", r"version=(\d+)", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--only-matching"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_list_conflicts() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "-L", "us", BUSTLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn word_regexp() -> TestResult {
+    let fox = fs::read_to_string(FOX)?;
+    Command::cargo_bin(PRG)?
+        .args(["-w", "the", FOX])
+        .assert()
+        .success()
+        .stdout(fox.clone());
+    Command::cargo_bin(PRG)?
+        .args(["--word-regexp", "he|row", FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["-wi", "QUICK|slow", FOX])
+        .assert()
+        .success()
+        .stdout(fox);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn line_regexp() -> TestResult {
+    let fox = fs::read_to_string(FOX)?;
+    Command::cargo_bin(PRG)?
+        .args(["-x", "the quick.*dog.", "-i", FOX])
+        .assert()
+        .success()
+        .stdout(fox);
+    Command::cargo_bin(PRG)?
+        .args(["--line-regexp", "The quick", FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_word_and_line_regexp() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-w", "-x", "fox", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-o", "[0-9]+"])
+        .write_stdin("a1b22c333\nnone\n")
+        .assert()
+        .success()
+        .stdout("1\n22\n333\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching_with_prefixes() -> TestResult {
+    let expected = "tests/inputs/bustle.txt:6:the\n\
+        tests/inputs/fox.txt:1:fox\n\
+        tests/inputs/fox.txt:1:the\n\
+        tests/inputs/fox.txt:1:dog\n";
+    Command::cargo_bin(PRG)?
+        .args(["-on", "fox|the|dog", BUSTLE, FOX])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching_count_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-oc", "[0-9]+"])
+        .write_stdin("a1b22c333\nnone\n4\n")
+        .assert()
+        .success()
+        .stdout("2\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn only_matching_inverted() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-ov", "fox", BUSTLE])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always() -> TestResult {
+    let expected = "\x1b[35mtests/inputs/bustle.txt\x1b[39m:\
+        \x1b[32m6\x1b[39m:\
+        The sweeping up \x1b[31;1mthe\x1b[0m heart,\n\
+        \x1b[35mtests/inputs/fox.txt\x1b[39m:\
+        \x1b[32m1\x1b[39m:\
+        The quick brown fox jumps over \x1b[31;1mthe\x1b[0m lazy dog.\n";
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "-n", "the", BUSTLE, FOX])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always_only_matching() -> TestResult {
+    let expected: String = ["ow", "ox", "ov", "og"]
+        .iter()
+        .map(|part| format!("\x1b[31;1m{}\x1b[0m\n", part))
+        .collect();
+    Command::cargo_bin(PRG)?
+        .args(["--colour=always", "-o", "o[a-z]", FOX])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_never() -> TestResult {
+    for args in [&["--color=never"][..], &["--color"], &[]] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .args(["-n", "The", BUSTLE, EMPTY, FOX, NOBODY])
+            .assert()
+            .success()
+            .stdout(fs::read_to_string(
+                "tests/expected/all.the.capitalized.line_number",
+            )?);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_stdin(args: &[&str], input: &str, expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .assert()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings() -> TestResult {
+    let input = "a.b(c) and more\naxbc\n2*3=6\n223\nC:\\temp\nC:temp\n";
+
+    run_stdin(&["-F", "a.b(c)", "-"], input, "a.b(c) and more\n")?;
+    run_stdin(&["a.b(c)", "-"], input, "axbc\n")?;
+
+    run_stdin(&["-F", "2*3", "-"], input, "2*3=6\n")?;
+    run_stdin(&["2*3", "-"], input, "2*3=6\n223\n")?;
+
+    run_stdin(&["--fixed-strings", "C:\\t", "-"], input, "C:\\temp\n")?;
+    run_stdin(&["C:\\t", "-"], input, "")?;
+
+    run_stdin(&["-Fi", "A.B(C)", "-"], input, "a.b(c) and more\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn fixed_strings_unbalanced() -> TestResult {
+    run_stdin(&["-F", "b(", "-"], "a.b(c)\nabc\n", "a.b(c)\n")?;
+    Command::cargo_bin(PRG)?
+        .args(["b(", "-"])
+        .write_stdin("a.b(c)\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid pattern \"b(\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_regexps() -> TestResult {
+    let expected = "tests/inputs/fox.txt:\
+        The quick brown fox jumps over the lazy dog.\n\
+        tests/inputs/nobody.txt:I'm Nobody! Who are you?\r\n\
+        tests/inputs/nobody.txt:Are you—Nobody—too?\r\n";
+    Command::cargo_bin(PRG)?
+        .args(["-e", "fox", "--regexp", "Nobody", FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn pattern_file() -> TestResult {
+    // "NOBODY" in the file only matches with -i
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-f", PATTERNS, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout("tests/inputs/fox.txt:1\ntests/inputs/nobody.txt:0\n");
+    Command::cargo_bin(PRG)?
+        .args(["-ci", "-f", PATTERNS, FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout("tests/inputs/fox.txt:1\ntests/inputs/nobody.txt:2\n");
+    Command::cargo_bin(PRG)?
+        .args(["-civ", "-f", PATTERNS, "-e", "pair", FOX, NOBODY])
+        .assert()
+        .success()
+        .stdout("tests/inputs/fox.txt:0\ntests/inputs/nobody.txt:6\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn pattern_file_empty_lines() -> TestResult {
+    // An empty pattern matches every line, and no patterns match none
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-f", "tests/patterns/empty_line.txt", BUSTLE])
+        .assert()
+        .success()
+        .stdout("9\n");
+    Command::cargo_bin(PRG)?
+        .args(["-f", "tests/patterns/empty.txt", BUSTLE])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_pattern_file() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad, FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!("{}: .* [(]os error 2[)]", bad))?);
+    Command::cargo_bin(PRG)?
+        .args(["-e", "fox", "-e", "(", FOX])
+        .assert()
+        .failure()
+        .stderr("Invalid pattern \"(\"\n");
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn sensitive_count_capital() -> TestResult {
@@ -234,7 +1411,7 @@ fn warns_dir_not_recursive() -> TestResult {
     let stdout = "tests/inputs/fox.txt:\
         The quick brown fox jumps over the lazy dog.";
     Command::cargo_bin(PRG)?
-        .args(&["fox", INPUTS_DIR, FOX])
+        .args(["fox", INPUTS_DIR, FOX])
         .assert()
         .stderr(predicate::str::contains("tests/inputs is a directory"))
         .stdout(predicate::str::contains(stdout));
@@ -271,7 +1448,7 @@ fn stdin_insensitive_count() -> TestResult {
     let expected = fs::read_to_string(expected_file)?;
 
     Command::cargo_bin(PRG)?
-        .args(&["-ci", "the", "-"])
+        .args(["-ci", "the", "-"])
         .write_stdin(input)
         .assert()
         .stdout(expected);
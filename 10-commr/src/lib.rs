@@ -1,14 +1,51 @@
 use std::{
+    borrow::Cow,
     cmp::Ordering,
+    collections::BinaryHeap,
     error::Error,
-    fs::File,
-    io::{self, BufRead, BufReader},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
 };
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use flate2::read::MultiGzDecoder;
+use serde_json::{json, Map, Value};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+// Which file's version of a common line to print in column 3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PrintFrom {
+    #[value(name = "1")]
+    File1,
+    #[value(name = "2")]
+    File2,
+    Both,
+}
+
+// The outcome of a comparison, which only affects the exit status with
+// --status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Same,
+    Differ,
+}
+
+impl Status {
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Same => 0,
+            Status::Differ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+}
+
 #[derive(Parser, Debug)]
 #[command(name = "commr")]
 #[command(author = "Ali Ahmed")]
@@ -21,6 +58,9 @@ pub struct Config {
     #[arg(help = "Second file, can be STDIN if file1 is not")]
     file2: String,
 
+    #[arg(help = "More files to compare with --multi", requires = "multi")]
+    more_files: Vec<String>,
+
     #[arg(help = "Suppress printing of column 1", short = '1', long)]
     suppress_col1: bool,
 
@@ -30,18 +70,219 @@ pub struct Config {
     #[arg(help = "Suppress printing of column 3", short = '3', long)]
     suppress_col3: bool,
 
+    #[arg(
+        help = "Print only lines in both files, like -12",
+        long,
+        conflicts_with_all = ["suppress_col1", "suppress_col2", "suppress_col3", "diff", "union"]
+    )]
+    intersect: bool,
+
+    #[arg(
+        help = "Print only lines unique to file1, like -23",
+        long,
+        conflicts_with_all = ["suppress_col1", "suppress_col2", "suppress_col3", "union"]
+    )]
+    diff: bool,
+
+    #[arg(
+        help = "Print every line once, without indenting the columns",
+        long,
+        conflicts_with_all = ["suppress_col1", "suppress_col2", "suppress_col3"]
+    )]
+    union: bool,
+
+    #[arg(
+        help = "Which file's version of common lines to print, as they may differ with -i or the whitespace options",
+        long,
+        value_enum,
+        default_value = "1",
+        conflicts_with = "multi"
+    )]
+    print_from: PrintFrom,
+
+    #[arg(
+        help = "Compare any number of files, printing each line once followed by the numbers of the files it is in",
+        long,
+        conflicts_with_all = ["suppress_col1", "suppress_col2", "suppress_col3", "intersect", "diff", "union"]
+    )]
+    multi: bool,
+
     #[arg(help = "Case-insensitive comparison of lines", short = 'i', long)]
     insensitive: bool,
 
-    #[arg(help = "Output delimiter", short = 'd', long, default_value = "\t")]
-    delimiter: String,
+    #[arg(
+        help = "Ignore whitespace, including carriage returns, at the end of lines when comparing",
+        long
+    )]
+    ignore_trailing_space: bool,
+
+    #[arg(help = "Compare runs of whitespace as a single space", long)]
+    ignore_all_space: bool,
+
+    #[arg(
+        help = "Compare lines as numbers, as sorted by sort -n; lines that aren't numbers sort after those that are",
+        short = 'n',
+        long
+    )]
+    numeric: bool,
+
+    #[arg(
+        help = "Output delimiter, which may use the escapes \\t, \\0, \\\\ and \\xNN",
+        short = 'd',
+        long,
+        default_value = "\t",
+        value_parser = parse_output_delimiter
+    )]
+    delimiter: Box<[u8]>,
+
+    #[arg(
+        help = "Compare lines on field N alone (1-based), or the whole line if it has no field N",
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    key: Option<u32>,
+
+    #[arg(
+        help = "Field delimiter for --key",
+        long,
+        value_name = "CHAR",
+        default_value = "\t",
+        requires = "key"
+    )]
+    field_delimiter: char,
+
+    #[arg(
+        help = "Check that the input is correctly sorted, even if all input lines are pairable",
+        long,
+        overrides_with = "nocheck_order"
+    )]
+    check_order: bool,
+
+    #[arg(
+        help = "Do not check that the input is correctly sorted",
+        long,
+        overrides_with = "check_order"
+    )]
+    nocheck_order: bool,
+
+    #[arg(
+        help = "Discard the first N lines of each file before comparing",
+        long,
+        value_name = "N",
+        default_value_t = 0
+    )]
+    skip_header: usize,
+
+    #[arg(
+        help = "Discard the first N lines of file1, overriding --skip-header",
+        long,
+        value_name = "N"
+    )]
+    skip_header1: Option<usize>,
+
+    #[arg(
+        help = "Discard the first N lines of file2, overriding --skip-header",
+        long,
+        value_name = "N"
+    )]
+    skip_header2: Option<usize>,
+
+    #[arg(help = "Print the lines skipped from file1 before the output", long)]
+    print_header: bool,
+
+    #[arg(
+        help = "Decompress input with gzip (detected automatically from the content or a .gz name)",
+        long
+    )]
+    gzip: bool,
+
+    #[arg(
+        help = "Keep a UTF-8 byte order mark at the start of an input, which is otherwise dropped",
+        long
+    )]
+    keep_bom: bool,
+
+    #[arg(
+        help = "Read each input BYTES at a time, with an optional K, M or G suffix",
+        long,
+        value_name = "BYTES",
+        default_value = "8K",
+        value_parser = parse_buffer_size
+    )]
+    buffer_size: usize,
+
+    #[arg(
+        help = "Pad the columns with spaces to line up, instead of indenting with the delimiter",
+        long,
+        conflicts_with_all = ["delimiter", "union", "multi"]
+    )]
+    align: bool,
+
+    #[arg(
+        help = "With --align, cut lines longer than N characters, marking them with …",
+        long,
+        value_name = "N",
+        requires = "align",
+        value_parser = clap::value_parser!(u32).range(2..)
+    )]
+    max_col_width: Option<u32>,
+
+    #[arg(
+        help = "Print each run of equal lines once, after the number of copies in file1 and in file2",
+        long,
+        conflicts_with_all = ["multi", "align", "union"]
+    )]
+    count: bool,
+
+    #[arg(
+        help = "Exit with 1 if any line is only in one file and 2 on errors, like cmp and diff",
+        long
+    )]
+    status: bool,
+
+    #[arg(
+        help = "Output format; json collects the lines of every shown column in memory and prints one document at the end",
+        long,
+        value_enum,
+        default_value = "text",
+        conflicts_with_all = ["multi", "print_header", "align"]
+    )]
+    format: Format,
+
+    #[arg(
+        help = "Write output to FILE instead of STDOUT",
+        short = 'o',
+        long,
+        value_name = "FILE"
+    )]
+    output: Option<String>,
 }
 
 pub fn get_args() -> MyResult<Config> {
     Ok(Config::parse())
 }
 
-pub fn run(config: Config) -> MyResult<()> {
+impl Config {
+    // Errors exit with 2 under --status, as 1 then means the files differ
+    pub fn error_code(&self) -> i32 {
+        if self.status {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn outcome(&self, differ: bool) -> Status {
+        if self.status && differ {
+            Status::Differ
+        } else {
+            Status::Same
+        }
+    }
+}
+
+pub fn run(config: Config) -> MyResult<Status> {
     let file1 = &config.file1;
     let file2 = &config.file2;
 
@@ -49,88 +290,748 @@ pub fn run(config: Config) -> MyResult<()> {
         return Err(From::from("Both input files cannot be STDIN (\"-\")"));
     }
 
-    let _file1 = open(file1)?;
-    let _file2 = open(file2)?;
+    if config.count && config.format == Format::Json {
+        return Err(From::from("--count cannot be used with --format json"));
+    }
+
+    if let Some(output) = &config.output {
+        let mut inputs = [file1, file2].into_iter().chain(&config.more_files);
+        if inputs.any(|input| same_file(output, input)) {
+            return Err(format!("Output file cannot be an input file (\"{}\")", output).into());
+        }
+    }
+
+    if config.multi {
+        return run_multi(&config);
+    }
+
+    let name1 = input_name(1, file1);
+    let name2 = input_name(2, file2);
+
+    let mut _file1 = open(file1, &name1, &config)?;
+    let mut _file2 = open(file2, &name2, &config)?;
 
-    let mut file1_iter = _file1.lines().map(|l| l.unwrap());
-    let mut file2_iter = _file2.lines().map(|l| l.unwrap());
+    let header = skip_lines(&mut _file1, &name1, header_lines(0, &config))?;
+    skip_lines(&mut _file2, &name2, header_lines(1, &config))?;
 
-    let mut file1_line = file1_iter.next();
-    let mut file2_line = file2_iter.next();
+    let mut out = create_output(&config)?;
+    print_header(&mut out, &header, &config)?;
+    let mut sink = create_sink(out, &config);
+
+    let mut file1_line = read_line(&mut _file1, &name1)?;
+    let mut file2_line = read_line(&mut _file2, &name2)?;
+
+    // Like GNU comm, only complain about unsorted input once a line has
+    // failed to pair up, unless --check-order asks for it regardless
+    let mut seen_unpairable = false;
+    let mut unsorted = [false; 2];
+
+    let mut check_order = |n: usize, prev: &Option<Vec<u8>>, next: &Option<Vec<u8>>, unpaired| {
+        if config.nocheck_order || !(config.check_order || unpaired) || unsorted[n - 1] {
+            return;
+        }
+        if let (Some(prev), Some(next)) = (prev, next) {
+            if compare_lines(prev, next, &config) == Ordering::Greater {
+                eprintln!("commr: file {} is not in sorted order", n);
+                unsorted[n - 1] = true;
+            }
+        }
+    };
 
     loop {
         match (&file1_line, &file2_line) {
-            (Some(line1), Some(line2)) => match compare_lines(line1, line2, config.insensitive) {
+            (Some(line1), Some(line2)) => match compare_lines(line1, line2, &config) {
                 Ordering::Less => {
-                    print_line_1(line1, &config);
-                    file1_line = file1_iter.next();
+                    let (count, next) = advance(&mut _file1, &name1, line1, &config)?;
+                    sink.add(1, &counted(line1, [count, 0], &config), &config)?;
+                    sink.tally(1);
+                    seen_unpairable = true;
+                    check_order(1, &file1_line, &next, seen_unpairable);
+                    file1_line = next;
                 }
                 Ordering::Greater => {
-                    print_line_2(line2, &config);
-                    file2_line = file2_iter.next();
+                    let (count, next) = advance(&mut _file2, &name2, line2, &config)?;
+                    sink.add(2, &counted(line2, [0, count], &config), &config)?;
+                    sink.tally(2);
+                    seen_unpairable = true;
+                    check_order(2, &file2_line, &next, seen_unpairable);
+                    file2_line = next;
                 }
                 Ordering::Equal => {
-                    print_line_3(line1, &config);
-                    file1_line = file1_iter.next();
-                    file2_line = file2_iter.next();
+                    let (count1, next1) = advance(&mut _file1, &name1, line1, &config)?;
+                    let (count2, next2) = advance(&mut _file2, &name2, line2, &config)?;
+                    let counts = [count1, count2];
+                    // With --print-from both the pair is two lines but one match
+                    sink.tally(3);
+                    if config.print_from != PrintFrom::File2 {
+                        sink.add(3, &counted(line1, counts, &config), &config)?;
+                    }
+                    if config.print_from != PrintFrom::File1 {
+                        sink.add(3, &counted(line2, counts, &config), &config)?;
+                    }
+                    check_order(1, &file1_line, &next1, seen_unpairable);
+                    file1_line = next1;
+                    check_order(2, &file2_line, &next2, seen_unpairable);
+                    file2_line = next2;
                 }
             },
             (Some(line1), None) => {
-                print_line_1(line1, &config);
-                file1_line = file1_iter.next();
+                let (count, next) = advance(&mut _file1, &name1, line1, &config)?;
+                sink.add(1, &counted(line1, [count, 0], &config), &config)?;
+                sink.tally(1);
+                seen_unpairable = true;
+                check_order(1, &file1_line, &next, seen_unpairable);
+                file1_line = next;
             }
             (None, Some(line2)) => {
-                print_line_2(line2, &config);
-                file2_line = file2_iter.next();
+                let (count, next) = advance(&mut _file2, &name2, line2, &config)?;
+                sink.add(2, &counted(line2, [0, count], &config), &config)?;
+                sink.tally(2);
+                seen_unpairable = true;
+                check_order(2, &file2_line, &next, seen_unpairable);
+                file2_line = next;
             }
             (None, None) => break,
         }
     }
 
-    Ok(())
+    sink.finish(&config)?;
+
+    if unsorted.contains(&true) {
+        return Err(From::from("input is not in sorted order"));
+    }
+
+    Ok(config.outcome(seen_unpairable))
 }
 
-fn print_line_1(line1: &str, config: &Config) {
-    if !config.suppress_col1 {
-        println!("{}", line1)
+// Reads past the given line, which with --count means past the whole run
+// of lines equal to it, returning how many were consumed and the line
+// after them
+fn advance(
+    file: &mut Box<dyn BufRead>,
+    name: &str,
+    line: &[u8],
+    config: &Config,
+) -> MyResult<(usize, Option<Vec<u8>>)> {
+    let mut count = 1;
+    let mut next = read_line(file, name)?;
+    if config.count {
+        while let Some(more) = &next {
+            if compare_lines(more, line, config) != Ordering::Equal {
+                break;
+            }
+            count += 1;
+            next = read_line(file, name)?;
+        }
     }
+    Ok((count, next))
 }
 
-fn print_line_2(line2: &str, config: &Config) {
-    if !config.suppress_col2 {
-        if config.suppress_col1 {
-            println!("{}", line2);
-        } else {
-            println!("{}{}", config.delimiter, line2);
+// With --count, puts the number of copies in each file before the line
+fn counted<'a>(line: &'a [u8], counts: [usize; 2], config: &Config) -> Cow<'a, [u8]> {
+    if !config.count {
+        return Cow::Borrowed(line);
+    }
+
+    let mut row = Vec::new();
+    for count in counts {
+        row.extend_from_slice(count.to_string().as_bytes());
+        row.extend_from_slice(&config.delimiter);
+    }
+    row.extend_from_slice(line);
+    Cow::Owned(row)
+}
+
+// Where the merge sends each line, tagged with its column
+trait OutputSink {
+    fn add(&mut self, column: usize, line: &[u8], config: &Config) -> io::Result<()>;
+
+    // Called once for each line, or pair of matching lines, in a column
+    fn tally(&mut self, _column: usize) {}
+
+    // Writes anything held back until the end and flushes the output
+    fn finish(&mut self, config: &Config) -> io::Result<()>;
+}
+
+fn create_sink(out: Box<dyn Write>, config: &Config) -> Box<dyn OutputSink> {
+    if config.format == Format::Json {
+        Box::new(JsonSink {
+            out,
+            lines: Default::default(),
+            counts: [0; 3],
+        })
+    } else if config.align {
+        Box::new(AlignedSink {
+            out,
+            rows: Vec::new(),
+            widths: [None; 3],
+        })
+    } else {
+        Box::new(PlainSink { out })
+    }
+}
+
+// Prints each line as it comes, indented with the delimiter
+struct PlainSink {
+    out: Box<dyn Write>,
+}
+
+impl OutputSink for PlainSink {
+    fn add(&mut self, column: usize, line: &[u8], config: &Config) -> io::Result<()> {
+        print_column(&mut self.out, column, line, config)
+    }
+
+    fn finish(&mut self, _config: &Config) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// Holds every shown line so that each column can be padded to the width
+// of its longest line, up to --max-col-width
+struct AlignedSink {
+    out: Box<dyn Write>,
+    rows: Vec<(usize, Vec<u8>)>,
+    widths: [Option<usize>; 3],
+}
+
+const ALIGN_GAP: usize = 2;
+
+impl OutputSink for AlignedSink {
+    fn add(&mut self, column: usize, line: &[u8], config: &Config) -> io::Result<()> {
+        if show_column(column, config) {
+            let width = fit_width(line, config).1;
+            let max = self.widths[column - 1].get_or_insert(0);
+            *max = width.max(*max);
+            self.rows.push((column, line.to_vec()));
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, config: &Config) -> io::Result<()> {
+        for (column, line) in &self.rows {
+            // Columns with no lines at all take up no room
+            let indent: usize = self.widths[..column - 1]
+                .iter()
+                .flatten()
+                .map(|width| width + ALIGN_GAP)
+                .sum();
+            let (text, _) = fit_width(line, config);
+            print_line(&mut self.out, " ".repeat(indent).as_bytes(), &text)?;
         }
+        self.out.flush()
     }
 }
 
-fn print_line_3(line3: &str, config: &Config) {
-    if !config.suppress_col3 {
-        if config.suppress_col1 && config.suppress_col2 {
-            println!("{}", line3);
-        } else if config.suppress_col1 || config.suppress_col2 {
-            println!("{}{}", config.delimiter, line3)
-        } else {
-            println!("{}{}{}", config.delimiter, config.delimiter, line3)
+// A line as --align prints it, cut to --max-col-width, with its width
+// in characters
+fn fit_width<'a>(line: &'a [u8], config: &Config) -> (Cow<'a, [u8]>, usize) {
+    let text = String::from_utf8_lossy(line);
+    let width = text.chars().count();
+    match config.max_col_width.map(|max| max as usize) {
+        Some(max) if width > max => {
+            let mut cut: String = text.chars().take(max - 1).collect();
+            cut.push('…');
+            (Cow::Owned(cut.into_bytes()), max)
+        }
+        _ => (Cow::Borrowed(line), width),
+    }
+}
+
+// Gathers the columns for --format json. Only the lines of shown columns
+// are kept, but every column is counted
+struct JsonSink {
+    out: Box<dyn Write>,
+    lines: [Vec<String>; 3],
+    counts: [usize; 3],
+}
+
+impl OutputSink for JsonSink {
+    fn add(&mut self, column: usize, line: &[u8], config: &Config) -> io::Result<()> {
+        if show_column(column, config) {
+            self.lines[column - 1].push(String::from_utf8_lossy(line).into_owned());
+        }
+        Ok(())
+    }
+
+    fn tally(&mut self, column: usize) {
+        self.counts[column - 1] += 1;
+    }
+
+    fn finish(&mut self, config: &Config) -> io::Result<()> {
+        let names = ["only_first", "only_second", "both"];
+        let mut doc = Map::new();
+        for (column, lines) in self.lines.iter().enumerate() {
+            if show_column(column + 1, config) {
+                doc.insert(names[column].to_string(), json!(lines));
+            }
         }
+        doc.insert(
+            "counts".to_string(),
+            json!({
+                "only_first": self.counts[0],
+                "only_second": self.counts[1],
+                "both": self.counts[2],
+            }),
+        );
+
+        serde_json::to_writer(&mut self.out, &Value::Object(doc))?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+// The next line of one file in the --multi merge, ordered so that the
+// max-heap pops the smallest line first, and the lowest file on ties
+struct Head<'a> {
+    line: Vec<u8>,
+    file: usize,
+    config: &'a Config,
+}
+
+impl Ord for Head<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_lines(&other.line, &self.line, self.config).then(other.file.cmp(&self.file))
     }
 }
 
-fn compare_lines(line1: &str, line2: &str, insensitive: bool) -> Ordering {
-    if insensitive {
-        line1.to_lowercase().cmp(&line2.to_lowercase())
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head<'_> {}
+
+fn run_multi(config: &Config) -> MyResult<Status> {
+    let filenames: Vec<&str> = [&config.file1, &config.file2]
+        .into_iter()
+        .chain(&config.more_files)
+        .map(String::as_str)
+        .collect();
+
+    if filenames
+        .iter()
+        .filter(|&&filename| filename == "-")
+        .count()
+        > 1
+    {
+        return Err(From::from("Only one input file can be STDIN (\"-\")"));
+    }
+
+    let names: Vec<String> = filenames
+        .iter()
+        .enumerate()
+        .map(|(file, filename)| input_name(file + 1, filename))
+        .collect();
+
+    let mut files = filenames
+        .iter()
+        .zip(&names)
+        .map(|(filename, name)| open(filename, name, config))
+        .collect::<MyResult<Vec<_>>>()?;
+
+    let mut header = Vec::new();
+    for (file, reader) in files.iter_mut().enumerate() {
+        let skipped = skip_lines(reader, &names[file], header_lines(file, config))?;
+        if file == 0 {
+            header = skipped;
+        }
+    }
+
+    let mut out = create_output(config)?;
+    print_header(&mut out, &header, config)?;
+
+    let mut heap = BinaryHeap::new();
+    for (file, reader) in files.iter_mut().enumerate() {
+        if let Some(line) = read_line(reader, &names[file])? {
+            heap.push(Head { line, file, config });
+        }
+    }
+
+    let mut seen_unpairable = false;
+    let mut unsorted = vec![false; files.len()];
+
+    while let Some(first) = heap.pop() {
+        let mut group = vec![first];
+        while heap.peek().is_some_and(|head| {
+            compare_lines(&head.line, &group[0].line, config) == Ordering::Equal
+        }) {
+            group.extend(heap.pop());
+        }
+
+        let members: Vec<String> = group
+            .iter()
+            .map(|head| (head.file + 1).to_string())
+            .collect();
+        out.write_all(&group[0].line)?;
+        out.write_all(&config.delimiter)?;
+        out.write_all(members.join(",").as_bytes())?;
+        out.write_all(b"\n")?;
+
+        seen_unpairable |= group.len() < files.len();
+        let check = !config.nocheck_order && (config.check_order || seen_unpairable);
+
+        for head in group {
+            if let Some(line) = read_line(&mut files[head.file], &names[head.file])? {
+                if check
+                    && !unsorted[head.file]
+                    && compare_lines(&head.line, &line, config) == Ordering::Greater
+                {
+                    eprintln!("commr: file {} is not in sorted order", head.file + 1);
+                    unsorted[head.file] = true;
+                }
+                heap.push(Head {
+                    line,
+                    file: head.file,
+                    config,
+                });
+            }
+        }
+    }
+
+    out.flush()?;
+
+    if unsorted.contains(&true) {
+        return Err(From::from("input is not in sorted order"));
+    }
+
+    Ok(config.outcome(seen_unpairable))
+}
+
+// The number of header lines to skip in the file with the given index
+fn header_lines(file: usize, config: &Config) -> usize {
+    match file {
+        0 => config.skip_header1.unwrap_or(config.skip_header),
+        1 => config.skip_header2.unwrap_or(config.skip_header),
+        _ => config.skip_header,
+    }
+}
+
+fn skip_lines(file: &mut Box<dyn BufRead>, name: &str, n: usize) -> MyResult<Vec<Vec<u8>>> {
+    let mut lines = Vec::new();
+    while lines.len() < n {
+        match read_line(file, name)? {
+            Some(line) => lines.push(line),
+            None => break,
+        }
+    }
+    Ok(lines)
+}
+
+fn print_header(out: &mut dyn Write, header: &[Vec<u8>], config: &Config) -> io::Result<()> {
+    if config.print_header {
+        for line in header {
+            print_line(out, b"", line)?;
+        }
+    }
+    Ok(())
+}
+
+fn create_output(config: &Config) -> MyResult<Box<dyn Write>> {
+    match &config.output {
+        Some(output) => Ok(Box::new(BufWriter::new(
+            File::create(output).map_err(|e| format!("{}: {}", output, e))?,
+        ))),
+        None => Ok(Box::new(io::stdout())),
+    }
+}
+
+fn show_column(column: usize, config: &Config) -> bool {
+    match column {
+        1 => !(config.suppress_col1 || config.intersect),
+        2 => !(config.suppress_col2 || config.intersect || config.diff),
+        _ => !(config.suppress_col3 || config.diff),
+    }
+}
+
+// Each column is indented by one delimiter per shown column before it,
+// except with --union, which merges them all, and --count, whose counts
+// already tell the columns apart
+fn print_column(
+    out: &mut dyn Write,
+    column: usize,
+    line: &[u8],
+    config: &Config,
+) -> io::Result<()> {
+    if !show_column(column, config) {
+        return Ok(());
+    }
+
+    let indent = if config.union || config.count {
+        0
     } else {
+        (1..column).filter(|&col| show_column(col, config)).count()
+    };
+    print_line(out, &config.delimiter.repeat(indent), line)
+}
+
+// Lines are written as the bytes that were read, valid UTF-8 or not
+fn print_line(out: &mut dyn Write, indent: &[u8], line: &[u8]) -> io::Result<()> {
+    out.write_all(indent)?;
+    out.write_all(line)?;
+    out.write_all(b"\n")
+}
+
+fn compare_lines(line1: &[u8], line2: &[u8], config: &Config) -> Ordering {
+    let key1 = normalize_space(select_key(line1, config), config);
+    let key2 = normalize_space(select_key(line2, config), config);
+    let (line1, line2): (&[u8], &[u8]) = (&key1, &key2);
+
+    if config.numeric {
+        match (parse_number(line1), parse_number(line2)) {
+            (Some(num1), Some(num2)) => return num1.partial_cmp(&num2).unwrap(),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => {}
+        }
+    }
+
+    if config.insensitive {
+        let line1 = String::from_utf8_lossy(line1).to_lowercase();
+        let line2 = String::from_utf8_lossy(line2).to_lowercase();
         line1.cmp(&line2)
+    } else {
+        line1.cmp(line2)
     }
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(
-            File::open(filename).map_err(|e| format!("{}: {}", filename, e))?,
-        ))),
+fn normalize_space<'a>(key: &'a [u8], config: &Config) -> Cow<'a, [u8]> {
+    let key = if config.ignore_trailing_space {
+        key.trim_ascii_end()
+    } else {
+        key
+    };
+
+    if !config.ignore_all_space {
+        return Cow::Borrowed(key);
+    }
+
+    let mut collapsed = Vec::with_capacity(key.len());
+    for &byte in key {
+        if !byte.is_ascii_whitespace() {
+            collapsed.push(byte);
+        } else if collapsed.last() != Some(&b' ') {
+            collapsed.push(b' ');
+        }
+    }
+    Cow::Owned(collapsed)
+}
+
+fn parse_number(key: &[u8]) -> Option<f64> {
+    std::str::from_utf8(key)
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|num| !num.is_nan())
+}
+
+fn select_key<'a>(line: &'a [u8], config: &Config) -> &'a [u8] {
+    match config.key {
+        Some(n) => {
+            let mut buf = [0; 4];
+            let delimiter = config.field_delimiter.encode_utf8(&mut buf).as_bytes();
+            nth_field(line, delimiter, n as usize).unwrap_or(line)
+        }
+        None => line,
+    }
+}
+
+fn nth_field<'a>(line: &'a [u8], delimiter: &[u8], n: usize) -> Option<&'a [u8]> {
+    let find = |field: &[u8]| {
+        field
+            .windows(delimiter.len())
+            .position(|window| window == delimiter)
+    };
+
+    let mut field = line;
+    for _ in 1..n {
+        field = &field[find(field)? + delimiter.len()..];
+    }
+    Some(match find(field) {
+        Some(pos) => &field[..pos],
+        None => field,
+    })
+}
+
+// Reads the next line without its line ending, or None at the end of the file
+fn read_line(file: &mut Box<dyn BufRead>, name: &str) -> MyResult<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    let bytes = file
+        .read_until(b'\n', &mut line)
+        .map_err(|e| format!("{}: {}", name, e))?;
+
+    if bytes == 0 {
+        return Ok(None);
+    }
+    // A \r before the newline is kept, so CRLF lines are written back as
+    // they were read and only --ignore-trailing-space looks past it
+    if line.ends_with(b"\n") {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+// Compares the paths as given and, when both exist, as resolved on disk
+fn same_file(path1: &str, path2: &str) -> bool {
+    if path1 == path2 {
+        return true;
+    }
+    match (fs::canonicalize(path1), fs::canonicalize(path2)) {
+        (Ok(path1), Ok(path2)) => path1 == path2,
+        _ => false,
+    }
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Names an input in error messages by its argument as well as its path,
+// so that standard input and the other file can be told apart
+fn input_name(file: usize, filename: &str) -> String {
+    format!("file{}: {}", file, filename)
+}
+
+// Escapes let a tab, a NUL or any other byte be passed portably
+fn parse_output_delimiter(input: &str) -> Result<Box<[u8]>, String> {
+    let invalid = || format!("Invalid delimiter \"{}\"", input);
+
+    let mut delimiter = Vec::new();
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            delimiter.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('t') => delimiter.push(b'\t'),
+            Some('0') => delimiter.push(b'\0'),
+            Some('\\') => delimiter.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                if hex.len() != 2 {
+                    return Err(invalid());
+                }
+                delimiter.push(u8::from_str_radix(&hex, 16).map_err(|_| invalid())?);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(delimiter.into_boxed_slice())
+}
+
+fn parse_buffer_size(input: &str) -> Result<usize, String> {
+    let (digits, unit) = match input.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&input[..i], 1024),
+        Some((i, 'M' | 'm')) => (&input[..i], 1024 * 1024),
+        Some((i, 'G' | 'g')) => (&input[..i], 1024 * 1024 * 1024),
+        _ => (input, 1),
+    };
+
+    match digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+    {
+        Some(0) => Err("buffer size must be positive".to_string()),
+        Some(size) => Ok(size),
+        None => Err(format!("Invalid buffer size \"{}\"", input)),
+    }
+}
+
+// Only one line per file is held at a time, besides these buffers, so
+// memory use doesn't grow with the inputs
+fn open(filename: &str, name: &str, config: &Config) -> MyResult<Box<dyn BufRead>> {
+    let source: Box<dyn Read> = match filename {
+        "-" => Box::new(io::stdin()),
+        _ => Box::new(File::open(filename).map_err(|e| format!("{}: {}", name, e))?),
+    };
+    let mut reader = BufReader::with_capacity(config.buffer_size, source);
+
+    let magic = reader.fill_buf().map_err(|e| format!("{}: {}", name, e))?;
+    let reader: Box<dyn BufRead> =
+        if config.gzip || filename.ends_with(".gz") || magic.starts_with(&GZIP_MAGIC) {
+            Box::new(BufReader::with_capacity(
+                config.buffer_size,
+                MultiGzDecoder::new(reader),
+            ))
+        } else {
+            Box::new(reader)
+        };
+
+    if config.keep_bom {
+        Ok(reader)
+    } else {
+        Ok(strip_bom(reader).map_err(|e| format!("{}: {}", name, e))?)
+    }
+}
+
+const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+// Drops a byte order mark so that the first line compares as if it had
+// none, putting back whatever else was read to look for it
+fn strip_bom(mut reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    let mut start = Vec::with_capacity(UTF8_BOM.len());
+    reader
+        .by_ref()
+        .take(UTF8_BOM.len() as u64)
+        .read_to_end(&mut start)?;
+
+    if start == UTF8_BOM {
+        Ok(reader)
+    } else {
+        Ok(Box::new(io::Cursor::new(start).chain(reader)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_buffer_size, parse_output_delimiter};
+
+    #[test]
+    fn test_parse_output_delimiter() {
+        let parse = |input| parse_output_delimiter(input).map(|d| d.into_vec());
+        assert_eq!(parse(""), Ok(vec![]));
+        assert_eq!(parse(":"), Ok(b":".to_vec()));
+        assert_eq!(parse("||"), Ok(b"||".to_vec()));
+        assert_eq!(parse("\t"), Ok(b"\t".to_vec()));
+        assert_eq!(parse("\\t"), Ok(b"\t".to_vec()));
+        assert_eq!(parse("\\0"), Ok(b"\0".to_vec()));
+        assert_eq!(parse("\\\\"), Ok(b"\\".to_vec()));
+        assert_eq!(parse("\\x3a\\x3A"), Ok(b"::".to_vec()));
+        assert_eq!(parse("\\xff"), Ok(vec![0xff]));
+        assert_eq!(parse("a\\tb"), Ok(b"a\tb".to_vec()));
+        assert_eq!(parse("→"), Ok("→".as_bytes().to_vec()));
+
+        for bad in ["\\", "\\n", "\\x", "\\x4", "\\xzz", "a\\"] {
+            let res = parse(bad);
+            assert_eq!(res.unwrap_err(), format!("Invalid delimiter \"{}\"", bad));
+        }
+    }
+
+    #[test]
+    fn test_parse_buffer_size() {
+        assert_eq!(parse_buffer_size("1"), Ok(1));
+        assert_eq!(parse_buffer_size("4096"), Ok(4096));
+        assert_eq!(parse_buffer_size("8K"), Ok(8 * 1024));
+        assert_eq!(parse_buffer_size("8k"), Ok(8 * 1024));
+        assert_eq!(parse_buffer_size("2M"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_buffer_size("1G"), Ok(1024 * 1024 * 1024));
+
+        let res = parse_buffer_size("0");
+        assert_eq!(res.unwrap_err(), "buffer size must be positive");
+        assert!(parse_buffer_size("0K").is_err());
+
+        for bad in ["", "K", "-1", "1.5M", "1T", "1KB", "foo"] {
+            let res = parse_buffer_size(bad);
+            assert_eq!(res.unwrap_err(), format!("Invalid buffer size \"{}\"", bad));
+        }
+        assert!(parse_buffer_size(&format!("{}G", usize::MAX)).is_err());
     }
 }
@@ -1,6 +1,18 @@
 fn main() {
-    if let Err(e) = commr::get_args().and_then(commr::run) {
-        eprint!("{}", e);
-        std::process::exit(1);
+    let config = match commr::get_args() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("commr: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let error_code = config.error_code();
+
+    match commr::run(config) {
+        Err(e) => {
+            eprintln!("commr: {}", e);
+            std::process::exit(error_code);
+        }
+        Ok(status) => std::process::exit(status.code()),
     }
 }
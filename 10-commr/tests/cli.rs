@@ -1,13 +1,43 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::fs;
+use serde_json::{json, Value};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+use tempfile::NamedTempFile;
 
 const PRG: &str = "commr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FILE1: &str = "tests/inputs/file1.txt";
 const FILE2: &str = "tests/inputs/file2.txt";
 const BLANK: &str = "tests/inputs/blank.txt";
+const FILE1_BOM: &str = "tests/inputs/file1_bom.txt";
+const FILE2_BOM: &str = "tests/inputs/file2_bom.txt";
+const FILE1_GZ: &str = "tests/inputs/file1.txt.gz";
+const FILE2_GZ: &str = "tests/inputs/file2.txt.gz";
+const UNSORTED: &str = "tests/inputs/unsorted.txt";
+const KEYS1: &str = "tests/inputs/keys1.csv";
+const KEYS2: &str = "tests/inputs/keys2.csv";
+const KEYS3: &str = "tests/inputs/keys3.csv";
+const NUMBERS1: &str = "tests/inputs/numbers1.txt";
+const NUMBERS2: &str = "tests/inputs/numbers2.txt";
+const NUMKEYS1: &str = "tests/inputs/numkeys1.csv";
+const NUMKEYS2: &str = "tests/inputs/numkeys2.csv";
+const MULTI1: &str = "tests/inputs/multi1.txt";
+const MULTI2: &str = "tests/inputs/multi2.txt";
+const MULTI3: &str = "tests/inputs/multi3.txt";
+const HEADER1: &str = "tests/inputs/header1.csv";
+const HEADER2: &str = "tests/inputs/header2.csv";
+const HEADER3: &str = "tests/inputs/header3.csv";
+const DUPS1: &str = "tests/inputs/dups1.txt";
+const DUPS2: &str = "tests/inputs/dups2.txt";
+const CRLF: &str = "tests/inputs/crlf.txt";
+const LF: &str = "tests/inputs/lf.txt";
+const SPACES1: &str = "tests/inputs/spaces1.txt";
+const SPACES2: &str = "tests/inputs/spaces2.txt";
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -40,9 +70,9 @@ fn gen_bad_file() -> String {
 #[test]
 fn dies_bad_file1() -> TestResult {
     let bad = gen_bad_file();
-    let expected = format!("{}: .* [(]os error 2[)]", bad);
+    let expected = format!("^commr: file1: {}: .* [(]os error 2[)]\n$", bad);
     Command::cargo_bin(PRG)?
-        .args(&[&bad, FILE1])
+        .args([&bad, FILE1])
         .assert()
         .failure()
         .stderr(predicate::str::is_match(expected)?);
@@ -53,9 +83,9 @@ fn dies_bad_file1() -> TestResult {
 #[test]
 fn dies_bad_file2() -> TestResult {
     let bad = gen_bad_file();
-    let expected = format!("{}: .* [(]os error 2[)]", bad);
+    let expected = format!("^commr: file2: {}: .* [(]os error 2[)]\n$", bad);
     Command::cargo_bin(PRG)?
-        .args(&[FILE1, &bad])
+        .args([FILE1, &bad])
         .assert()
         .failure()
         .stderr(predicate::str::is_match(expected)?);
@@ -65,12 +95,33 @@ fn dies_bad_file2() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn dies_both_stdin() -> TestResult {
-    let expected = "Both input files cannot be STDIN (\"-\")";
+    let expected = "commr: Both input files cannot be STDIN (\"-\")\n";
     Command::cargo_bin(PRG)?
-        .args(&["-", "-"])
+        .args(["-", "-"])
         .assert()
-        .failure()
-        .stderr(predicate::str::contains(expected));
+        .code(1)
+        .stdout("")
+        .stderr(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_stdin_bad_file() -> TestResult {
+    // The bad file is named by its argument, whichever side stdin is on
+    let bad = gen_bad_file();
+    for (args, expected) in [
+        (["-", &bad], format!("^commr: file2: {}: .* [(]os error 2[)]\n$", bad)),
+        ([&bad, "-"], format!("^commr: file1: {}: .* [(]os error 2[)]\n$", bad)),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .write_stdin("a\n")
+            .assert()
+            .code(1)
+            .stdout("")
+            .stderr(predicate::str::is_match(expected)?);
+    }
     Ok(())
 }
 
@@ -102,6 +153,18 @@ fn run_stdin(
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn stdin_file2_plain() -> TestResult {
+    run_stdin(&["-", FILE2], FILE1, "tests/expected/file1_file2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_stdin_plain() -> TestResult {
+    run_stdin(&[FILE1, "-"], FILE2, "tests/expected/file1_file2.out")
+}
+
 // --------------------------------------------------
 #[test]
 fn empty_empty() -> TestResult {
@@ -340,6 +403,917 @@ fn blank_file1() -> TestResult {
     run(&[BLANK, FILE1], "tests/expected/blank_file1.out")
 }
 
+// --------------------------------------------------
+fn run_unsorted(
+    args: &[&str],
+    expected_file: &str,
+    expected_err: &str,
+) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .code(1)
+        .stdout(expected)
+        .stderr(predicate::str::contains(expected_err));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_unsorted() -> TestResult {
+    run_unsorted(
+        &[FILE1, UNSORTED],
+        "tests/expected/file1_unsorted.out",
+        "commr: file 2 is not in sorted order",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_unsorted_check_order() -> TestResult {
+    run_unsorted(
+        &[FILE1, UNSORTED, "--check-order"],
+        "tests/expected/file1_unsorted.out",
+        "commr: file 2 is not in sorted order",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_unsorted_nocheck_order() -> TestResult {
+    run(
+        &[FILE1, UNSORTED, "--nocheck-order"],
+        "tests/expected/file1_unsorted.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_unsorted() -> TestResult {
+    // Every line pairs up, so the order only matters with --check-order
+    run(&[UNSORTED, UNSORTED], "tests/expected/unsorted_unsorted.out")?;
+    run_unsorted(
+        &[UNSORTED, UNSORTED, "--check-order"],
+        "tests/expected/unsorted_unsorted.out",
+        "commr: file 1 is not in sorted order",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn keys1_keys2() -> TestResult {
+    run(&[KEYS1, KEYS2], "tests/expected/keys1_keys2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn keys1_keys2_key1() -> TestResult {
+    run(
+        &[KEYS1, KEYS2, "--key", "1", "--field-delimiter", ","],
+        "tests/expected/keys1_keys2.key1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn keys1_keys3_key2() -> TestResult {
+    // "dave" has no second field in either file, so the whole lines match
+    run(
+        &[KEYS1, KEYS3, "--key", "2", "--field-delimiter", ","],
+        "tests/expected/keys1_keys3.key2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_key() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([KEYS1, KEYS2, "--key", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '0'"));
+    Command::cargo_bin(PRG)?
+        .args([KEYS1, KEYS2, "--field-delimiter", ","])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--key"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn numbers1_numbers2_n() -> TestResult {
+    run(
+        &[NUMBERS1, NUMBERS2, "-n", "--check-order"],
+        "tests/expected/numbers1_numbers2.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn numbers1_numbers2_lexical() -> TestResult {
+    // Compared as text, "10" sorts before "9"
+    Command::cargo_bin(PRG)?
+        .args([NUMBERS1, NUMBERS2])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("is not in sorted order"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn numkeys1_numkeys2_n_key1() -> TestResult {
+    run(
+        &[NUMKEYS1, NUMKEYS2, "-n", "--key", "1", "--field-delimiter", ","],
+        "tests/expected/numkeys1_numkeys2.n.key1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_latin2() -> TestResult {
+    // Not UTF-8, so the expected output is compared as bytes
+    let expected = fs::read("tests/expected/latin1_latin2.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/latin1.txt", "tests/inputs/latin2.txt"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_file2_dir() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([FILE1, "tests/inputs"])
+        .assert()
+        .code(1)
+        .stderr("commr: file2: tests/inputs: Is a directory (os error 21)\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_gz() -> TestResult {
+    run(&[FILE1_GZ, FILE2_GZ], "tests/expected/file1_file2.out")?;
+    run(&[FILE1_GZ, FILE2_GZ, "--gzip"], "tests/expected/file1_file2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_gz_mixed() -> TestResult {
+    run(&[FILE1, FILE2_GZ], "tests/expected/file1_file2.out")?;
+    run(&[FILE1_GZ, FILE2], "tests/expected/file1_file2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_gz_i() -> TestResult {
+    run(
+        &[FILE1_GZ, FILE2_GZ, "-123", "-i"],
+        "tests/expected/file1_file2.123.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_gz_file2() -> TestResult {
+    // Standard input has no name, so only the content gives it away
+    let input = fs::read(FILE1_GZ)?;
+    let expected = fs::read_to_string("tests/expected/file1_file2.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-", FILE2])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_outfile(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    let outfile = NamedTempFile::new()?;
+    let outpath = outfile.path().to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .args(["-o", outpath])
+        .assert()
+        .success()
+        .stdout("");
+
+    let contents = fs::read_to_string(outpath)?;
+    assert_eq!(expected, contents);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_outfile() -> TestResult {
+    run_outfile(&[FILE1, FILE2], "tests/expected/file1_file2.out")?;
+    run_outfile(
+        &[FILE1, FILE2, "-123", "-i"],
+        "tests/expected/file1_file2.123.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_file2_outfile() -> TestResult {
+    let input = fs::read_to_string(FILE1)?;
+    let expected = fs::read_to_string("tests/expected/file1_file2.out")?;
+    let outfile = NamedTempFile::new()?;
+    let outpath = outfile.path().to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args(["-", FILE2, "--output", outpath])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("");
+
+    assert_eq!(expected, fs::read_to_string(outpath)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_outfile_is_input() -> TestResult {
+    let before = fs::read_to_string(FILE2)?;
+    for outpath in [FILE2, "tests/inputs/../inputs/file2.txt"] {
+        Command::cargo_bin(PRG)?
+            .args([FILE1, FILE2, "-o", outpath])
+            .assert()
+            .failure()
+            .stderr(format!(
+                "commr: Output file cannot be an input file (\"{}\")\n",
+                outpath
+            ));
+    }
+
+    // The input must not have been truncated
+    assert_eq!(before, fs::read_to_string(FILE2)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_intersect() -> TestResult {
+    run(&[FILE1, FILE2, "--intersect"], "tests/expected/file1_file2.intersect.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_intersect_i() -> TestResult {
+    run(
+        &[FILE1, FILE2, "--intersect", "-i"],
+        "tests/expected/file1_file2.intersect.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_diff() -> TestResult {
+    run(&[FILE1, FILE2, "--diff"], "tests/expected/file1_file2.diff.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_diff_i() -> TestResult {
+    run(
+        &[FILE1, FILE2, "--diff", "-i"],
+        "tests/expected/file1_file2.diff.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_union() -> TestResult {
+    run(&[FILE1, FILE2, "--union"], "tests/expected/file1_file2.union.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_union_i() -> TestResult {
+    run(
+        &[FILE1, FILE2, "--union", "-i"],
+        "tests/expected/file1_file2.union.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_set_operation_conflicts() -> TestResult {
+    for args in [
+        ["--intersect", "-1"],
+        ["--diff", "-3"],
+        ["--union", "-2"],
+        ["--intersect", "--diff"],
+        ["--union", "--diff"],
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .args([FILE1, FILE2])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn multi1_multi2_multi3() -> TestResult {
+    run(
+        &["--multi", MULTI1, MULTI2, MULTI3],
+        "tests/expected/multi1_multi2_multi3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multi3_multi1_multi2_delim() -> TestResult {
+    run(
+        &["--multi", "-d", ":", MULTI3, MULTI1, MULTI2],
+        "tests/expected/multi3_multi1_multi2.delim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_multi() -> TestResult {
+    run_stdin(
+        &["--multi", MULTI1, "-", MULTI3],
+        MULTI2,
+        "tests/expected/multi1_multi2_multi3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_more_files_without_multi() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([MULTI1, MULTI2, MULTI3])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--multi"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn header1_header2_skip_header() -> TestResult {
+    // Only the headers differ, so everything lands in column 3
+    Command::cargo_bin(PRG)?
+        .args([HEADER1, HEADER2, "--skip-header", "1"])
+        .assert()
+        .success()
+        .stdout("\t\t1,alice\n\t\t2,bob\n\t\t3,carol\n");
+    Command::cargo_bin(PRG)?
+        .args([HEADER1, HEADER2, "--skip-header", "1", "-12"])
+        .assert()
+        .success()
+        .stdout("1,alice\n2,bob\n3,carol\n");
+    Command::cargo_bin(PRG)?
+        .args([HEADER1, HEADER2, "--skip-header", "1", "-3"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn header1_header2_print_header() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([HEADER1, HEADER2, "--skip-header", "1", "--print-header"])
+        .assert()
+        .success()
+        .stdout("id,name\n\t\t1,alice\n\t\t2,bob\n\t\t3,carol\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn header1_header3_skip_header12() -> TestResult {
+    run(
+        &[
+            HEADER1,
+            HEADER3,
+            "--skip-header1",
+            "1",
+            "--skip-header2",
+            "2",
+            "--print-header",
+        ],
+        "tests/expected/header1_header3.skip.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_lf_ignore_trailing_space() -> TestResult {
+    run(
+        &[CRLF, LF, "--ignore-trailing-space"],
+        "tests/expected/crlf_lf.trailing.out",
+    )?;
+    // Every line pairs up, so nothing is left in columns 1 and 2
+    Command::cargo_bin(PRG)?
+        .args([CRLF, LF, "--ignore-trailing-space", "-3"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf_kept() -> TestResult {
+    // Without --ignore-trailing-space the \r is part of the line, and it
+    // is written back out
+    Command::cargo_bin(PRG)?
+        .args([CRLF, CRLF])
+        .assert()
+        .success()
+        .stdout("\t\tapple\r\n\t\tbanana \r\n\t\tcherry\t\r\n\t\tfig\r\n");
+    Command::cargo_bin(PRG)?
+        .args([CRLF, LF, "-12"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn spaces1_spaces2_ignore_all_space() -> TestResult {
+    run(
+        &[SPACES1, SPACES2, "--ignore-all-space"],
+        "tests/expected/spaces1_spaces2.all.out",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args([SPACES1, SPACES2, "-3"])
+        .assert()
+        .success()
+        .stdout(" three\tfour\n\t three four\none  two\n\tone two\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_from1_i() -> TestResult {
+    run(
+        &[FILE1, FILE2, "-i", "--print-from", "1"],
+        "tests/expected/file1_file2.from1.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_from2_i() -> TestResult {
+    run(
+        &[FILE1, FILE2, "-i", "--print-from", "2"],
+        "tests/expected/file1_file2.from2.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_fromboth_i() -> TestResult {
+    run(
+        &[FILE1, FILE2, "-i", "--print-from", "both"],
+        "tests/expected/file1_file2.fromboth.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_12_fromboth_i() -> TestResult {
+    run(
+        &[FILE1, FILE2, "-12", "-i", "--print-from", "both", "-d", ":"],
+        "tests/expected/file1_file2.12.fromboth.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_print_from() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([FILE1, FILE2, "--print-from", "3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '3'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_json(args: &[&str]) -> Result<Value, Box<dyn std::error::Error>> {
+    let output = Command::cargo_bin(PRG)?
+        .args(args)
+        .args(["--format", "json"])
+        .output()?;
+    assert!(output.status.success());
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+// --------------------------------------------------
+fn json_matches_text(args: &[&str]) -> TestResult {
+    let output = Command::cargo_bin(PRG)?.args(args).output()?;
+    assert!(output.status.success());
+
+    let mut columns: [Vec<String>; 3] = Default::default();
+    for line in String::from_utf8(output.stdout)?.lines() {
+        match line.strip_prefix('\t') {
+            Some(rest) => match rest.strip_prefix('\t') {
+                Some(rest) => columns[2].push(rest.to_string()),
+                None => columns[1].push(rest.to_string()),
+            },
+            None => columns[0].push(line.to_string()),
+        }
+    }
+
+    let doc = run_json(args)?;
+    assert_eq!(doc["only_first"], json!(columns[0]));
+    assert_eq!(doc["only_second"], json!(columns[1]));
+    assert_eq!(doc["both"], json!(columns[2]));
+    assert_eq!(
+        doc["counts"],
+        json!({
+            "only_first": columns[0].len(),
+            "only_second": columns[1].len(),
+            "both": columns[2].len(),
+        })
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn json_format() -> TestResult {
+    json_matches_text(&[FILE1, FILE2])?;
+    json_matches_text(&[FILE1, FILE2, "-i"])?;
+    json_matches_text(&[FILE1, EMPTY])?;
+    json_matches_text(&[EMPTY, FILE2])?;
+    json_matches_text(&[KEYS1, KEYS2, "--key", "1", "--field-delimiter", ","])
+}
+
+// --------------------------------------------------
+#[test]
+fn json_format_suppressed() -> TestResult {
+    let doc = run_json(&[FILE1, FILE2, "-12"])?;
+    assert_eq!(
+        doc,
+        json!({
+            "both": ["c"],
+            "counts": {"only_first": 3, "only_second": 1, "both": 1},
+        })
+    );
+
+    let doc = run_json(&[FILE1, FILE2, "--diff", "-i"])?;
+    assert_eq!(
+        doc,
+        json!({
+            "only_first": ["a", "d"],
+            "counts": {"only_first": 2, "only_second": 0, "both": 2},
+        })
+    );
+
+    // Both lines of a pair are shown, but the pair counts once
+    let doc = run_json(&[FILE1, FILE2, "-12", "-i", "--print-from", "both"])?;
+    assert_eq!(
+        doc,
+        json!({
+            "both": ["b", "B", "c", "c"],
+            "counts": {"only_first": 2, "only_second": 0, "both": 2},
+        })
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn status_same() -> TestResult {
+    for args in [
+        vec![FILE1, FILE1],
+        vec![EMPTY, EMPTY],
+        vec![HEADER1, HEADER2, "--skip-header", "1", "-123"],
+        vec!["--multi", MULTI1, MULTI1, MULTI1],
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .arg("--status")
+            .assert()
+            .code(0);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn status_differ() -> TestResult {
+    for args in [
+        vec![FILE1, FILE2],
+        vec![FILE1, EMPTY],
+        // Decided by the merge, so hiding the columns doesn't matter
+        vec![FILE1, FILE2, "-12"],
+        vec![FILE1, FILE2, "--intersect", "-i"],
+        vec!["--multi", MULTI1, MULTI2, MULTI3],
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .arg("--status")
+            .assert()
+            .code(1);
+
+        // Without --status, differing files are still a success
+        Command::cargo_bin(PRG)?.args(&args).assert().code(0);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn status_error() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args([FILE1, &bad, "--status"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("os error 2"));
+    Command::cargo_bin(PRG)?
+        .args([FILE1, UNSORTED, "--status"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("not in sorted order"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn keys1_keys2_align() -> TestResult {
+    run(&[KEYS1, KEYS2, "--align"], "tests/expected/keys1_keys2.align.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn keys1_keys2_align_1() -> TestResult {
+    run(
+        &[KEYS1, KEYS2, "--align", "-1"],
+        "tests/expected/keys1_keys2.align.1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn keys1_keys2_align_max_col_width() -> TestResult {
+    run(
+        &[KEYS1, KEYS2, "--align", "--max-col-width", "5"],
+        "tests/expected/keys1_keys2.align.width5.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_align_conflicts() -> TestResult {
+    for args in [
+        vec!["--align", "-d", ":"],
+        vec!["--align", "--union"],
+        vec!["--align", "--format", "json"],
+        vec!["--max-col-width", "5"],
+        vec!["--align", "--max-col-width", "1"],
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .args([FILE1, FILE2])
+            .assert()
+            .failure();
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn buffer_size() -> TestResult {
+    for size in ["1", "3", "64K", "1M"] {
+        run(
+            &[FILE1, FILE2, "--buffer-size", size],
+            "tests/expected/file1_file2.out",
+        )?;
+        run(
+            &[FILE1_GZ, FILE2_GZ, "--buffer-size", size],
+            "tests/expected/file1_file2.out",
+        )?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_buffer_size() -> TestResult {
+    for (size, expected) in [
+        ("0", "buffer size must be positive"),
+        ("0M", "buffer size must be positive"),
+        ("12X", "Invalid buffer size \"12X\""),
+        ("1.5K", "Invalid buffer size \"1.5K\""),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args([FILE1, FILE2, "--buffer-size", size])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn gen_numbered_lines(path: &Path, step: usize) -> TestResult {
+    let mut file = BufWriter::new(File::create(path)?);
+    for i in 0..1_000_000 {
+        writeln!(file, "line {:020}", i * step)?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn merge_memory_is_bounded() -> TestResult {
+    // Two 27MB inputs under a 16MB address space limit, which is only
+    // enough if the merge holds a line per file rather than the files
+    let dir = tempfile::tempdir()?;
+    let (file1, file2) = (dir.path().join("by2.txt"), dir.path().join("by3.txt"));
+    gen_numbered_lines(&file1, 2)?;
+    gen_numbered_lines(&file2, 3)?;
+
+    let prg = assert_cmd::cargo::cargo_bin(PRG);
+    for size in ["8K", "1M"] {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg("ulimit -v 16000 && exec \"$0\" \"$@\"")
+            .arg(&prg)
+            .args(["-12", "--buffer-size", size])
+            .arg(&file1)
+            .arg(&file2)
+            .output()?;
+        assert!(output.status.success());
+
+        // Multiples of 6 up to 1,999,998
+        let stdout = String::from_utf8(output.stdout)?;
+        assert_eq!(stdout.lines().count(), 333_334);
+        assert_eq!(stdout.lines().last(), Some("line 00000000000001999998"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_multichar_delim() -> TestResult {
+    // Each line is indented by the whole delimiter once for every shown
+    // column before its own
+    for (flags, expected) in [
+        (vec![], "||B\na\nb\n||||c\nd\n"),
+        (vec!["-1"], "B\n||c\n"),
+        (vec!["-2"], "a\nb\n||c\nd\n"),
+        (vec!["-3"], "||B\na\nb\nd\n"),
+        (vec!["-12"], "c\n"),
+        (vec!["-13"], "B\n"),
+        (vec!["-23"], "a\nb\nd\n"),
+        (vec!["-123"], ""),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args([FILE1, FILE2, "-d", "||"])
+            .args(&flags)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_escaped_delim() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/file1_file2.out")?;
+    run(&[FILE1, FILE2, "-d", r"\t"], "tests/expected/file1_file2.out")?;
+    run(
+        &[FILE1, FILE2, "-d", r"\x3a"],
+        "tests/expected/file1_file2.delim.out",
+    )?;
+
+    // A NUL delimiter, for machine parsing
+    Command::cargo_bin(PRG)?
+        .args([FILE1, FILE2, "-d", r"\0"])
+        .assert()
+        .success()
+        .stdout(expected.replace('\t', "\0"));
+    Command::cargo_bin(PRG)?
+        .args([FILE1, FILE2, "-d", r"\\"])
+        .assert()
+        .success()
+        .stdout(expected.replace('\t', "\\"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_delim() -> TestResult {
+    for bad in [r"\n", r"\x4", r"\xzz", r"a\"] {
+        Command::cargo_bin(PRG)?
+            .args([FILE1, FILE2, "-d", bad])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "Invalid delimiter \"{}\"",
+                bad
+            )));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_bom() -> TestResult {
+    // Only the byte order mark differs, so every line is in column 3
+    run(&[FILE1_BOM, FILE1], "tests/expected/file1_file1.out")?;
+    run(&[FILE1, FILE1_BOM], "tests/expected/file1_file1.out")?;
+    run(&[FILE1_BOM, FILE2_BOM], "tests/expected/file1_file2.out")?;
+    run_stdin(&["-", FILE1], FILE1_BOM, "tests/expected/file1_file1.out")?;
+    run(
+        &[FILE1_BOM, FILE1, "--buffer-size", "1"],
+        "tests/expected/file1_file1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_bom_keep_bom() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([FILE1_BOM, FILE1, "--keep-bom", "--nocheck-order"])
+        .assert()
+        .success()
+        .stdout("\ta\n\tb\n\tc\n\td\n\u{feff}a\nb\nc\nd\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dups1_dups2() -> TestResult {
+    // Without --count, each copy pairs up with at most one other
+    run(&[DUPS1, DUPS2], "tests/expected/dups1_dups2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dups1_dups2_count() -> TestResult {
+    run(&[DUPS1, DUPS2, "--count"], "tests/expected/dups1_dups2.count.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dups1_dups2_count_3_delim() -> TestResult {
+    run(
+        &[DUPS1, DUPS2, "--count", "-3", "-d", ":"],
+        "tests/expected/dups1_dups2.count.3.delim.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_file2_count_i() -> TestResult {
+    // Runs are of lines that compare equal, so they follow -i
+    Command::cargo_bin(PRG)?
+        .args([FILE1, FILE2, "--count", "-i", "-12"])
+        .assert()
+        .success()
+        .stdout("1\t1\tb\n1\t1\tc\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_count_conflicts() -> TestResult {
+    for args in [
+        vec!["--count", "--align"],
+        vec!["--count", "--union"],
+        vec!["--count", "--multi"],
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .args([DUPS1, DUPS2])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Command::cargo_bin(PRG)?
+        .args([DUPS1, DUPS2, "--count", "--format", "json"])
+        .assert()
+        .failure()
+        .stderr("commr: --count cannot be used with --format json\n");
+    Ok(())
+}
+
 //// --------------------------------------------------
 //#[test]
 //fn file1_blanks() -> TestResult {
@@ -1,14 +1,50 @@
 use std::{
+    collections::VecDeque,
     error::Error,
-    fs::File,
-    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    fs::{self, File, Metadata},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    mem,
     str::FromStr,
+    thread,
+    time::Duration,
 };
 
 use clap::{ArgGroup, Parser};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+// The outcome of a run: a file that could not be opened or read is a
+// failure, after its error has been printed and the other files shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+}
+
+impl Status {
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Success => 0,
+            Status::Failure => 1,
+        }
+    }
+}
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+// Multipliers for the -c/-n suffixes, with the two-letter ones checked first
+const SUFFIXES: [(&str, i64); 9] = [
+    ("kB", 1000),
+    ("MB", 1000 * 1000),
+    ("GB", 1000 * 1000 * 1000),
+    ("b", 512),
+    ("k", 1024),
+    ("K", 1024),
+    ("M", 1024 * 1024),
+    ("G", 1024 * 1024 * 1024),
+    ("", 1),
+];
+
 #[derive(Debug, PartialEq, Clone)]
 enum TakeValue {
     // represents an argument of +0
@@ -36,6 +72,79 @@ pub struct Config {
 
     #[arg(short = 'q', long)]
     quiet: bool,
+
+    #[arg(short = 'v', long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    #[arg(short = 'f', long)]
+    follow: bool,
+
+    // same as --follow --retry
+    #[arg(short = 'F')]
+    follow_name: bool,
+
+    #[arg(long)]
+    retry: bool,
+
+    #[arg(short = 's', long, value_parser = parse_duration, default_value = "1.0")]
+    sleep_interval: Duration,
+}
+
+impl Config {
+    fn following(&self) -> bool {
+        self.follow || self.follow_name
+    }
+
+    fn retrying(&self) -> bool {
+        self.retry || self.follow_name
+    }
+
+    fn headers(&self) -> Headers {
+        Headers {
+            enabled: self.verbose || (!self.quiet && self.files.len() > 1),
+            current: None,
+        }
+    }
+}
+
+// Prints a `==> name <==` header whenever the output switches to another file,
+// with a blank line between the output of each file
+struct Headers {
+    enabled: bool,
+    current: Option<usize>,
+}
+
+impl Headers {
+    fn switch_to(&mut self, out: &mut impl Write, file_num: usize, name: &str) -> MyResult<()> {
+        if self.enabled && self.current != Some(file_num) {
+            writeln!(
+                out,
+                "{}==> {} <==",
+                if self.current.is_some() { "\n" } else { "" },
+                name
+            )?;
+        }
+        self.current = Some(file_num);
+        Ok(())
+    }
+}
+
+// Standard input, pipes and devices can only be streamed, while regular files
+// can be counted and seeked
+enum Input {
+    Stream(Box<dyn BufRead>),
+    File(File),
+}
+
+// A file being watched in follow mode, with the offset of the last byte printed.
+// `file` is None while a retried path is missing, and `id` is the (device, inode)
+// pair used to notice the path being replaced by a new file.
+struct Followed {
+    file_num: usize,
+    name: String,
+    file: Option<File>,
+    id: Option<(u64, u64)>,
+    offset: u64,
 }
 
 fn parse_line(s: &str) -> Result<TakeValue, String> {
@@ -46,6 +155,14 @@ fn parse_byte(s: &str) -> Result<TakeValue, String> {
     FromStr::from_str(s).map_err(|_| format!("illegal byte count -- {}", s))
 }
 
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|secs| *secs > 0.0)
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("invalid number of seconds -- {}", s))
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct ParseTakeValueError;
 
@@ -53,16 +170,22 @@ impl FromStr for TakeValue {
     type Err = ParseTakeValueError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "+0" => Ok(TakeValue::PlusZero),
-            pos if pos.starts_with("+") => Ok(TakeValue::TakeNum(
-                s.parse::<i64>().map_err(|_| ParseTakeValueError)?,
-            )),
-            _ => Ok(TakeValue::TakeNum(
-                s.parse::<i64>()
-                    .map(|v| i64::wrapping_neg(v))
-                    .map_err(|_| ParseTakeValueError)?,
-            )),
+        let (num, unit) = SUFFIXES
+            .iter()
+            .find_map(|(suffix, unit)| s.strip_suffix(suffix).map(|num| (num, *unit)))
+            .ok_or(ParseTakeValueError)?;
+        let value = num
+            .parse::<i64>()
+            .ok()
+            .and_then(|v| v.checked_mul(unit))
+            // i64::MIN has no positive counterpart to count with
+            .filter(|v| v.checked_abs().is_some())
+            .ok_or(ParseTakeValueError)?;
+
+        match value {
+            0 if num.starts_with('+') => Ok(TakeValue::PlusZero),
+            _ if num.starts_with('+') || value < 0 => Ok(TakeValue::TakeNum(value)),
+            _ => Ok(TakeValue::TakeNum(-value)),
         }
     }
 }
@@ -72,37 +195,216 @@ pub fn get_args() -> MyResult<Config> {
     Ok(Config::parse())
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let num_files = config.files.len();
+pub fn run(config: Config) -> MyResult<Status> {
+    let mut headers = config.headers();
+    let mut followed = Vec::new();
+    let mut failed = false;
+    // As in tail, a missing file is only waited for while following
+    if config.retry && !config.following() {
+        eprintln!("tailr: warning: --retry ignored; --retry is useful only when following");
+    }
     for (file_num, filename) in config.files.iter().enumerate() {
-        match File::open(&filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(file) => {
-                if !config.quiet && num_files > 1 {
-                    println!(
-                        "{}==> {} <==",
-                        if file_num > 0 { "\n" } else { "" },
-                        filename
-                    )
+        match open(filename) {
+            Err(err) => {
+                eprintln!("tailr: {}: {}", filename, err);
+                // A retried file may still appear, so it isn't a failure yet
+                if config.following() && config.retrying() {
+                    followed.push(Followed {
+                        file_num,
+                        name: filename.to_string(),
+                        file: None,
+                        id: None,
+                        offset: 0,
+                    });
+                } else {
+                    failed = true;
                 }
-
-                let (total_lines, total_bytes) = count_lines_bytes(&filename)?;
-                let file = BufReader::new(file);
-                if let Some(num_bytes) = &config.bytes {
-                    print_bytes(file, num_bytes, total_bytes)?;
+            }
+            Ok(input) => {
+                let name = if filename == "-" {
+                    "standard input"
                 } else {
-                    print_lines(file, &config.lines, total_lines)?
+                    filename
+                };
+                headers.switch_to(&mut io::stdout(), file_num, name)?;
+
+                match tail(input, &config) {
+                    Err(err) => {
+                        eprintln!("tailr: {}: {}", filename, err);
+                        failed = true;
+                    }
+                    Ok(Some((file, offset))) if config.following() => {
+                        followed.push(Followed {
+                            file_num,
+                            name: filename.to_string(),
+                            id: file_id(&file.metadata()?),
+                            file: Some(file),
+                            offset,
+                        });
+                    }
+                    Ok(_) => {}
                 }
             }
         }
     }
 
+    if !followed.is_empty() {
+        follow(followed, headers, &config)?;
+    }
+
+    io::stdout().flush()?;
+    if failed {
+        Ok(Status::Failure)
+    } else {
+        Ok(Status::Success)
+    }
+}
+
+// ----------------------------------------------------------------------------------
+// Prints the tail of one input, returning a regular file with the offset
+// reached so that it can be followed
+fn tail(input: Input, config: &Config) -> MyResult<Option<(File, u64)>> {
+    let file = match input {
+        Input::File(file) => file,
+        Input::Stream(stream) => {
+            if let Some(num_bytes) = &config.bytes {
+                print_stream_bytes(stream, num_bytes)?;
+            } else {
+                print_stream_lines(stream, &config.lines)?;
+            }
+            return Ok(None);
+        }
+    };
+
+    // Only the +N line forms need to count the lines up front
+    let mut file = BufReader::new(file);
+    let total_bytes = if let Some(num_bytes) = &config.bytes {
+        let total_bytes = file.get_ref().metadata()?.len() as i64;
+        print_bytes(&mut file, num_bytes, total_bytes)?;
+        total_bytes
+    } else if let TakeValue::TakeNum(k @ ..=-1) = config.lines {
+        print_last_lines(&mut file, k.unsigned_abs())?;
+        0
+    } else {
+        let (total_lines, total_bytes) = count_lines_bytes(&mut file)?;
+        print_lines(&mut file, &config.lines, total_lines)?;
+        total_bytes
+    };
+
+    // The tail reads to the end of the file, which may have grown past the
+    // counted total in the meantime
+    let offset = file.stream_position()?.max(total_bytes as u64);
+    Ok(Some((file.into_inner(), offset)))
+}
+
+// ----------------------------------------------------------------------------------
+fn follow(mut files: Vec<Followed>, mut headers: Headers, config: &Config) -> MyResult<()> {
+    io::stdout().flush()?;
+    loop {
+        thread::sleep(config.sleep_interval);
+        for followed in files.iter_mut() {
+            let buffer = read_appended(followed)?;
+            if !buffer.is_empty() {
+                let mut stdout = io::stdout().lock();
+                headers.switch_to(&mut stdout, followed.file_num, &followed.name)?;
+                stdout.write_all(&buffer)?;
+                stdout.flush()?;
+            }
+
+            // Any data written to the old file before it was rotated away has
+            // been printed above, so the new file is read on the next pass
+            if config.retrying() {
+                reopen_if_replaced(followed)?;
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------------
+fn read_appended(followed: &mut Followed) -> MyResult<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let Some(file) = &mut followed.file else {
+        return Ok(buffer);
+    };
+
+    let len = file.metadata()?.len();
+    if len < followed.offset {
+        eprintln!("tailr: {}: file truncated", followed.name);
+        followed.offset = len;
+    } else if len > followed.offset {
+        file.seek(SeekFrom::Start(followed.offset))?;
+        file.take(len - followed.offset).read_to_end(&mut buffer)?;
+        followed.offset += buffer.len() as u64;
+    }
+    Ok(buffer)
+}
+
+// ----------------------------------------------------------------------------------
+fn reopen_if_replaced(followed: &mut Followed) -> MyResult<()> {
+    match fs::metadata(&followed.name) {
+        Err(err) => {
+            if followed.file.take().is_some() {
+                eprintln!(
+                    "tailr: '{}' has become inaccessible: {}",
+                    followed.name, err
+                );
+                followed.id = None;
+            }
+        }
+        Ok(meta) if followed.file.is_none() || followed.id != file_id(&meta) => {
+            // The path may vanish again between the stat and the open
+            let Ok(file) = File::open(&followed.name) else {
+                return Ok(());
+            };
+            eprintln!(
+                "tailr: '{}' has {}; following new file",
+                followed.name,
+                if followed.file.is_some() {
+                    "been replaced"
+                } else {
+                    "appeared"
+                }
+            );
+            followed.id = file_id(&file.metadata()?);
+            followed.file = Some(file);
+            followed.offset = 0;
+        }
+        Ok(_) => {}
+    }
     Ok(())
 }
 
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+// Without an inode to compare, a replaced file is only noticed once the
+// path goes missing
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
 // ----------------------------------------------------------------------------------
-fn count_lines_bytes(filename: &str) -> MyResult<(i64, i64)> {
-    let mut file = BufReader::new(File::open(filename)?);
+fn open(filename: &str) -> MyResult<Input> {
+    match filename {
+        "-" => Ok(Input::Stream(Box::new(BufReader::new(io::stdin())))),
+        _ => {
+            let file = File::open(filename)?;
+            if file.metadata()?.is_file() {
+                Ok(Input::File(file))
+            } else {
+                Ok(Input::Stream(Box::new(BufReader::new(file))))
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------------
+// Counts through the same handle the tail is read from, then rewinds it
+fn count_lines_bytes(file: &mut (impl BufRead + Seek)) -> MyResult<(i64, i64)> {
     let mut num_lines = 0;
     let mut num_bytes = 0;
     let mut buf = Vec::new();
@@ -115,51 +417,154 @@ fn count_lines_bytes(filename: &str) -> MyResult<(i64, i64)> {
         num_bytes += bytes_read as i64;
         buf.clear();
     }
+    file.seek(SeekFrom::Start(0))?;
     Ok((num_lines, num_bytes))
 }
 
 fn print_lines(mut file: impl BufRead, num_lines: &TakeValue, total_lines: i64) -> MyResult<()> {
     if let Some(start) = get_starting_index(num_lines, total_lines) {
-        let mut line_num = 0;
         let mut buf = Vec::new();
-        loop {
-            let bytes_read = file.read_until(b'\n', &mut buf)?;
-
-            if bytes_read == 0 {
+        for _ in 0..start {
+            if file.read_until(b'\n', &mut buf)? == 0 {
                 break;
             }
-            if line_num >= start {
-                print!("{}", String::from_utf8_lossy(&buf));
-            }
-            line_num += 1;
             buf.clear();
         }
+        io::copy(&mut file, &mut io::stdout().lock())?;
     }
     Ok(())
 }
 
+fn print_last_lines<T: BufRead + Seek>(file: &mut T, num_lines: u64) -> MyResult<()> {
+    let start = find_tail_start(file, num_lines)?;
+    file.seek(SeekFrom::Start(start))?;
+    io::copy(file, &mut io::stdout().lock())?;
+    Ok(())
+}
+
+// Reads backwards from the end in chunks to find the offset of the last lines
+fn find_tail_start<T: Read + Seek>(file: &mut T, num_lines: u64) -> MyResult<u64> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut pos = len;
+    let mut newlines = 0;
+    let mut buf = vec![0; CHUNK_SIZE as usize];
+    while pos > 0 && newlines < num_lines {
+        let size = pos.min(CHUNK_SIZE);
+        pos -= size;
+        file.seek(SeekFrom::Start(pos))?;
+        let chunk = &mut buf[..size as usize];
+        file.read_exact(chunk)?;
+        for (i, _) in chunk.iter().enumerate().rev().filter(|(_, &b)| b == b'\n') {
+            let next = pos + i as u64 + 1;
+            // The final newline ends the last line rather than starting a new one
+            if next == len {
+                continue;
+            }
+            newlines += 1;
+            if newlines == num_lines {
+                return Ok(next);
+            }
+        }
+    }
+    Ok(0)
+}
+
 fn print_bytes<T: Read + Seek>(
     mut file: T,
     num_bytes: &TakeValue,
     total_bytes: i64,
 ) -> MyResult<()> {
-    if let Some(start) = get_starting_index(&num_bytes, total_bytes) {
+    if let Some(start) = get_starting_index(num_bytes, total_bytes) {
         file.seek(SeekFrom::Start(start))?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-        if !buffer.is_empty() {
-            print!("{}", String::from_utf8_lossy(&buffer));
+        io::copy(&mut file, &mut io::stdout().lock())?;
+    }
+
+    Ok(())
+}
+
+// Streams can't be counted up front, so the last lines/bytes are kept in a
+// ring buffer while reading, and the +N forms skip ahead and copy the rest
+fn print_stream_lines(mut input: impl BufRead, num_lines: &TakeValue) -> MyResult<()> {
+    let keep = match *num_lines {
+        TakeValue::TakeNum(0) => return Ok(()),
+        TakeValue::TakeNum(k) if k > 0 => {
+            let mut buf = Vec::new();
+            for _ in 1..k {
+                if input.read_until(b'\n', &mut buf)? == 0 {
+                    break;
+                }
+                buf.clear();
+            }
+            io::copy(&mut input, &mut io::stdout().lock())?;
+            return Ok(());
+        }
+        TakeValue::TakeNum(k) => k.unsigned_abs() as usize,
+        // +0 is the same as +1, printing everything
+        TakeValue::PlusZero => {
+            io::copy(&mut input, &mut io::stdout().lock())?;
+            return Ok(());
+        }
+    };
+
+    let mut ring = VecDeque::new();
+    let mut buf = Vec::new();
+    while input.read_until(b'\n', &mut buf)? > 0 {
+        ring.push_back(mem::take(&mut buf));
+        if ring.len() > keep {
+            buf = ring.pop_front().unwrap_or_default();
+            buf.clear();
         }
     }
 
+    let mut stdout = io::stdout().lock();
+    for line in ring {
+        stdout.write_all(&line)?;
+    }
+    Ok(())
+}
+
+fn print_stream_bytes(mut input: impl BufRead, num_bytes: &TakeValue) -> MyResult<()> {
+    let keep = match *num_bytes {
+        TakeValue::TakeNum(0) => return Ok(()),
+        TakeValue::TakeNum(k) if k > 0 => {
+            io::copy(&mut (&mut input).take(k as u64 - 1), &mut io::sink())?;
+            io::copy(&mut input, &mut io::stdout().lock())?;
+            return Ok(());
+        }
+        TakeValue::TakeNum(k) => k.unsigned_abs() as usize,
+        // +0 is the same as +1, printing everything
+        TakeValue::PlusZero => {
+            io::copy(&mut input, &mut io::stdout().lock())?;
+            return Ok(());
+        }
+    };
+
+    let mut ring = VecDeque::new();
+    loop {
+        let chunk = input.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        let len = chunk.len();
+        ring.extend(chunk);
+        input.consume(len);
+        if ring.len() > keep {
+            ring.drain(..ring.len() - keep);
+        }
+    }
+
+    let (front, back) = ring.as_slices();
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(front)?;
+    stdout.write_all(back)?;
     Ok(())
 }
 
 fn get_starting_index(take_val: &TakeValue, total: i64) -> Option<u64> {
     match take_val {
         TakeValue::PlusZero if total == 0 => None,
-        TakeValue::PlusZero => Some((total - 1) as u64),
-        TakeValue::TakeNum(k) if total == 0 || *k > total => None,
+        TakeValue::PlusZero => Some(0),
+        TakeValue::TakeNum(k) if total == 0 || *k == 0 || *k > total => None,
         TakeValue::TakeNum(k) if *k < 0 && k.abs() > total => Some(0),
         TakeValue::TakeNum(k) if *k < 0 => Some((total + *k) as u64),
         TakeValue::TakeNum(k) => Some((*k - 1) as u64),
@@ -169,17 +574,162 @@ fn get_starting_index(take_val: &TakeValue, total: i64) -> Option<u64> {
 // -----------------------------------------------------------------------------------
 #[cfg(test)]
 mod tests {
-    use crate::{count_lines_bytes, get_starting_index, TakeValue};
+    use crate::{
+        count_lines_bytes, find_tail_start, get_starting_index, parse_duration,
+        ParseTakeValueError, TakeValue, CHUNK_SIZE,
+    };
+    use std::{
+        fs::{self, File},
+        io::{BufReader, Cursor, Read, Seek, SeekFrom},
+        str::FromStr,
+        time::Duration,
+    };
+
+    // Wraps a reader to count how many bytes are actually read from it
+    struct CountingReader<T> {
+        inner: T,
+        bytes_read: u64,
+    }
+
+    impl<T: Read> Read for CountingReader<T> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.bytes_read += n as u64;
+            Ok(n)
+        }
+    }
+
+    impl<T: Seek> Seek for CountingReader<T> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
 
     #[test]
     fn test_count_lines_bytes() {
-        let res = count_lines_bytes("tests/inputs/one.txt");
+        let mut file = BufReader::new(File::open("tests/inputs/one.txt").unwrap());
+        let res = count_lines_bytes(&mut file);
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), (1, 24));
 
-        let res = count_lines_bytes("tests/inputs/ten.txt");
+        let mut file = BufReader::new(File::open("tests/inputs/ten.txt").unwrap());
+        let res = count_lines_bytes(&mut file);
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), (10, 49));
+
+        // The last line need not end with a newline
+        let res = count_lines_bytes(&mut Cursor::new("a\nb"));
+        assert_eq!(res.unwrap(), (2, 3));
+    }
+
+    #[test]
+    fn test_count_lines_bytes_rewinds() {
+        // The handle is left at the start, ready to read the tail from
+        let mut file = BufReader::new(File::open("tests/inputs/ten.txt").unwrap());
+        assert_eq!(count_lines_bytes(&mut file).unwrap(), (10, 49));
+        assert_eq!(count_lines_bytes(&mut file).unwrap(), (10, 49));
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!(
+            contents,
+            fs::read_to_string("tests/inputs/ten.txt").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_take_value() {
+        let parse = |s| TakeValue::from_str(s);
+        assert_eq!(parse("3"), Ok(TakeValue::TakeNum(-3)));
+        assert_eq!(parse("-3"), Ok(TakeValue::TakeNum(-3)));
+        assert_eq!(parse("+3"), Ok(TakeValue::TakeNum(3)));
+        assert_eq!(parse("0"), Ok(TakeValue::TakeNum(0)));
+        assert_eq!(parse("+0"), Ok(TakeValue::PlusZero));
+
+        // Each suffix multiplies the number
+        assert_eq!(parse("2b"), Ok(TakeValue::TakeNum(-1024)));
+        assert_eq!(parse("2k"), Ok(TakeValue::TakeNum(-2048)));
+        assert_eq!(parse("2K"), Ok(TakeValue::TakeNum(-2048)));
+        assert_eq!(parse("2M"), Ok(TakeValue::TakeNum(-2 * 1024 * 1024)));
+        assert_eq!(parse("2G"), Ok(TakeValue::TakeNum(-2 * 1024 * 1024 * 1024)));
+        assert_eq!(parse("2kB"), Ok(TakeValue::TakeNum(-2000)));
+        assert_eq!(parse("2MB"), Ok(TakeValue::TakeNum(-2_000_000)));
+        assert_eq!(parse("2GB"), Ok(TakeValue::TakeNum(-2_000_000_000)));
+
+        // The sign conventions are unchanged
+        assert_eq!(parse("+1k"), Ok(TakeValue::TakeNum(1024)));
+        assert_eq!(parse("-2M"), Ok(TakeValue::TakeNum(-2 * 1024 * 1024)));
+        assert_eq!(parse("+0k"), Ok(TakeValue::PlusZero));
+
+        // Unknown suffixes, bad case, missing numbers, and overflow are errors
+        for bad in [
+            "10x", "10m", "10g", "10kb", "10B", "k", "+k", "-", "1.5k", "", "foo",
+        ] {
+            assert_eq!(parse(bad), Err(ParseTakeValueError), "{}", bad);
+        }
+        assert_eq!(
+            parse("9223372036854775807"),
+            Ok(TakeValue::TakeNum(-i64::MAX))
+        );
+        assert_eq!(parse("9223372036854775807k"), Err(ParseTakeValueError));
+        assert_eq!(parse("+9007199254740992G"), Err(ParseTakeValueError));
+        assert_eq!(parse("-9223372036854775808"), Err(ParseTakeValueError));
+        assert_eq!(parse("-4611686018427387904k"), Err(ParseTakeValueError));
+    }
+
+    #[test]
+    fn test_find_tail_start() {
+        let start = |text: &str, n| find_tail_start(&mut Cursor::new(text), n).unwrap();
+        assert_eq!(start("", 1), 0);
+        assert_eq!(start("a\nb\nc\n", 1), 4);
+        assert_eq!(start("a\nb\nc\n", 2), 2);
+        assert_eq!(start("a\nb\nc\n", 3), 0);
+        assert_eq!(start("a\nb\nc\n", 4), 0);
+
+        // The last line need not end with a newline
+        assert_eq!(start("a\nb\nc", 1), 4);
+        assert_eq!(start("a\nb\nc", 2), 2);
+
+        // Empty lines count as lines
+        assert_eq!(start("\n\n\n", 1), 2);
+        assert_eq!(start("\n\n\n", 2), 1);
+    }
+
+    #[test]
+    fn test_find_tail_start_reads_only_the_tail() {
+        // 100MB of short lines, of which only the last chunk should be read
+        let text = "line\n".repeat(20 * 1024 * 1024);
+        let mut reader = CountingReader {
+            inner: Cursor::new(text.as_bytes()),
+            bytes_read: 0,
+        };
+        let start = find_tail_start(&mut reader, 10).unwrap();
+        assert_eq!(start, text.len() as u64 - 50);
+        assert!(reader.bytes_read <= CHUNK_SIZE);
+
+        // Lines spanning several chunks are found by reading further back
+        let text = format!("{}\n{}\n", "a".repeat(200_000), "b".repeat(200_000));
+        let mut reader = CountingReader {
+            inner: Cursor::new(text.as_bytes()),
+            bytes_read: 0,
+        };
+        assert_eq!(find_tail_start(&mut reader, 1).unwrap(), 200_001);
+        assert!(reader.bytes_read <= 4 * CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("0.5"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_duration(".05"), Ok(Duration::from_millis(50)));
+
+        // Zero, negative, and non-numeric intervals are rejected
+        for bad in ["0", "-1", "-0.5", "NaN", "inf", "1e400", "", "1s", "foo"] {
+            assert_eq!(
+                parse_duration(bad),
+                Err(format!("invalid number of seconds -- {}", bad))
+            );
+        }
     }
 
     #[test]
@@ -187,13 +737,16 @@ mod tests {
         // +0 from an empty file (0 lines/bytes) returns None
         assert_eq!(get_starting_index(&TakeValue::PlusZero, 0), None);
 
-        // +0 from a nonempty file returns an index that
-        // is one less than the number of lines/bytes
+        // +0 from a nonempty file starts at the beginning, the same as +1
         assert_eq!(get_starting_index(&TakeValue::PlusZero, 1), Some(0));
+        assert_eq!(get_starting_index(&TakeValue::PlusZero, 10), Some(0));
 
         // Taking 0 lines/bytes returns None
         assert_eq!(get_starting_index(&TakeValue::TakeNum(1), 0), None);
 
+        // Taking 0 lines/bytes from a nonempty file returns None
+        assert_eq!(get_starting_index(&TakeValue::TakeNum(0), 10), None);
+
         // Taking more lines/bytes than is available returns None
         assert_eq!(get_starting_index(&TakeValue::TakeNum(2), 1), None);
 
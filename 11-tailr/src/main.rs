@@ -1,6 +1,9 @@
 fn main() {
-    if let Err(e) = tailr::get_args().and_then(tailr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match tailr::get_args().and_then(tailr::run) {
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        Ok(status) => std::process::exit(status.code()),
     }
 }
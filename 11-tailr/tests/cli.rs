@@ -1,8 +1,13 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::fs::{self, File};
-use std::io::Read;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::process::{Child, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::{tempdir, NamedTempFile};
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -12,6 +17,8 @@ const ONE: &str = "tests/inputs/one.txt";
 const TWO: &str = "tests/inputs/two.txt";
 const THREE: &str = "tests/inputs/three.txt";
 const TEN: &str = "tests/inputs/ten.txt";
+const LONG: &str = "tests/inputs/long.txt";
+const BINARY: &str = "tests/inputs/binary.bin";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -32,6 +39,63 @@ fn gen_bad_file() -> String {
     }
 }
 
+// --------------------------------------------------
+struct Follower {
+    child: Child,
+    stdout: Receiver<Vec<u8>>,
+    stderr: Receiver<Vec<u8>>,
+}
+
+// --------------------------------------------------
+fn spawn_follow(args: &[&str]) -> Result<Follower, Box<dyn std::error::Error>> {
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = forward(child.stdout.take().unwrap());
+    let stderr = forward(child.stderr.take().unwrap());
+    Ok(Follower {
+        child,
+        stdout,
+        stderr,
+    })
+}
+
+// --------------------------------------------------
+fn forward(mut stream: impl Read + Send + 'static) -> Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 1024];
+        while let Ok(n @ 1..) = stream.read(&mut buf) {
+            if tx.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// --------------------------------------------------
+fn wait_for(rx: &Receiver<Vec<u8>>, seen: &mut String, expected: &str) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !seen.contains(expected) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(left) {
+            Ok(chunk) => seen.push_str(&String::from_utf8_lossy(&chunk)),
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+// --------------------------------------------------
+fn append(path: &std::path::Path, text: &str) -> TestResult {
+    let mut file = OpenOptions::new().append(true).open(path)?;
+    file.write_all(text.as_bytes())?;
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn dies_no_args() -> TestResult {
@@ -49,7 +113,7 @@ fn dies_bad_bytes() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal byte count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-c", &bad, EMPTY])
+        .args(["-c", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -63,7 +127,7 @@ fn dies_bad_lines() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal line count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-n", &bad, EMPTY])
+        .args(["-n", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -78,7 +142,7 @@ fn dies_bytes_and_lines() -> TestResult {
                used with '--bytes <BYTES>'";
 
     Command::cargo_bin(PRG)?
-        .args(&["-n", "1", "-c", "2", "tests/inputs/empty.txt"])
+        .args(["-n", "1", "-c", "2", "tests/inputs/empty.txt"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(msg));
@@ -92,7 +156,7 @@ fn skips_bad_file() -> TestResult {
     let bad = gen_bad_file();
     let expected = format!("{}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
-        .args(&[ONE, &bad, TWO])
+        .args([ONE, &bad, TWO])
         .assert()
         .stderr(predicate::str::is_match(expected)?);
 
@@ -101,16 +165,27 @@ fn skips_bad_file() -> TestResult {
 
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> TestResult {
-    // Extra work here due to lossy UTF
-    let mut file = File::open(expected_file)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    let expected = String::from_utf8_lossy(&buffer);
+    let expected = fs::read(expected_file)?;
+
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .stdout(predicate::eq(expected));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_stdin(args: &[&str], input_file: &str, expected_file: &str) -> TestResult {
+    let input = fs::read(input_file)?;
+    let expected = fs::read(expected_file)?;
 
     Command::cargo_bin(PRG)?
         .args(args)
+        .write_stdin(input)
         .assert()
-        .stdout(predicate::eq(expected.as_bytes() as &[u8]));
+        .success()
+        .stdout(predicate::eq(expected));
 
     Ok(())
 }
@@ -829,3 +904,568 @@ fn multiple_files_c_plus_3() -> TestResult {
         "tests/expected/all.c+3.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn follow_prints_appended_lines() -> TestResult {
+    let file = NamedTempFile::new()?;
+    append(file.path(), "one\ntwo\n")?;
+    let path = file.path().to_str().unwrap();
+    let mut tailr = spawn_follow(&["-f", "-n", "1", path])?;
+
+    let mut seen = String::new();
+    let ok = wait_for(&tailr.stdout, &mut seen, "two\n");
+    if ok {
+        append(file.path(), "three\nfour\n")?;
+    }
+    let ok = ok && wait_for(&tailr.stdout, &mut seen, "four\n");
+    tailr.child.kill()?;
+    tailr.child.wait()?;
+
+    assert!(ok, "unexpected output: {:?}", seen);
+    assert_eq!(seen, "two\nthree\nfour\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_multiple_files_prints_headers() -> TestResult {
+    let file1 = NamedTempFile::new()?;
+    let file2 = NamedTempFile::new()?;
+    let path1 = file1.path().to_str().unwrap();
+    let path2 = file2.path().to_str().unwrap();
+    let mut tailr = spawn_follow(&["-f", path1, path2])?;
+
+    let mut seen = String::new();
+    let header2 = format!("==> {} <==\n", path2);
+    let ok = wait_for(&tailr.stdout, &mut seen, &header2);
+    if ok {
+        append(file1.path(), "first\n")?;
+    }
+    let ok = ok && wait_for(&tailr.stdout, &mut seen, "first\n");
+    if ok {
+        append(file2.path(), "second\n")?;
+    }
+    let ok = ok && wait_for(&tailr.stdout, &mut seen, "second\n");
+    tailr.child.kill()?;
+    tailr.child.wait()?;
+
+    assert!(ok, "unexpected output: {:?}", seen);
+    let expected = format!(
+        "==> {} <==\n\n==> {} <==\n\n==> {} <==\nfirst\n\n==> {} <==\nsecond\n",
+        path1, path2, path1, path2
+    );
+    assert_eq!(seen, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_reports_truncation() -> TestResult {
+    let file = NamedTempFile::new()?;
+    append(file.path(), "one\ntwo\nthree\n")?;
+    let path = file.path().to_str().unwrap();
+    let mut tailr = spawn_follow(&["-f", path])?;
+
+    let mut seen = String::new();
+    let mut errors = String::new();
+    let ok = wait_for(&tailr.stdout, &mut seen, "three\n");
+    if ok {
+        file.as_file().set_len(0)?;
+    }
+    let msg = format!("tailr: {}: file truncated", path);
+    let ok = ok && wait_for(&tailr.stderr, &mut errors, &msg);
+    if ok {
+        append(file.path(), "four\n")?;
+    }
+    let ok = ok && wait_for(&tailr.stdout, &mut seen, "four\n");
+    tailr.child.kill()?;
+    tailr.child.wait()?;
+
+    assert!(ok, "unexpected output: {:?} {:?}", seen, errors);
+    assert_eq!(seen, "one\ntwo\nthree\nfour\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_retry_waits_for_missing_file() -> TestResult {
+    let dir = tempdir()?;
+    let path = dir.path().join("later.log");
+    let name = path.to_str().unwrap();
+    let mut tailr = spawn_follow(&["-F", name])?;
+
+    let mut errors = String::new();
+    let ok = wait_for(&tailr.stderr, &mut errors, "No such file");
+    if ok {
+        fs::write(&path, "hello\n")?;
+    }
+    let msg = format!("tailr: '{}' has appeared; following new file", name);
+    let ok = ok && wait_for(&tailr.stderr, &mut errors, &msg);
+    let mut seen = String::new();
+    let ok = ok && wait_for(&tailr.stdout, &mut seen, "hello\n");
+    tailr.child.kill()?;
+    tailr.child.wait()?;
+
+    assert!(ok, "unexpected output: {:?} {:?}", seen, errors);
+    assert_eq!(seen, "hello\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_retry_survives_rotation() -> TestResult {
+    let dir = tempdir()?;
+    let path = dir.path().join("app.log");
+    let name = path.to_str().unwrap();
+    fs::write(&path, "old\n")?;
+    let mut tailr = spawn_follow(&["-F", name])?;
+
+    let mut seen = String::new();
+    let ok = wait_for(&tailr.stdout, &mut seen, "old\n");
+    if ok {
+        append(&path, "last old\n")?;
+        fs::rename(&path, dir.path().join("app.log.1"))?;
+        fs::write(&path, "new\n")?;
+    }
+    let ok = ok && wait_for(&tailr.stdout, &mut seen, "new\n");
+    tailr.child.kill()?;
+    tailr.child.wait()?;
+
+    assert!(ok, "unexpected output: {:?}", seen);
+    assert_eq!(seen, "old\nlast old\nnew\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn retry_without_follow_is_ignored() -> TestResult {
+    // As in tail, the missing file is reported at once and the rest shown
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--retry", &bad, ONE])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .failure()
+        .stdout(format!("==> {} <==\nÖne line, four wordś.\n", ONE))
+        .stderr(predicate::str::starts_with(
+            "tailr: warning: --retry ignored; --retry is useful only when following\n",
+        ))
+        .stderr(predicate::str::contains(format!("tailr: {}: ", bad)));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_sleep_interval() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-s=-1", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid number of seconds -- -1"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_sleep_interval() -> TestResult {
+    let file = NamedTempFile::new()?;
+    append(file.path(), "one\n")?;
+    let path = file.path().to_str().unwrap();
+    let mut tailr = spawn_follow(&["-f", "-s", "0.05", path])?;
+
+    let mut seen = String::new();
+    let ok = wait_for(&tailr.stdout, &mut seen, "one\n");
+    let start = Instant::now();
+    if ok {
+        append(file.path(), "two\n")?;
+    }
+    let ok = ok && wait_for(&tailr.stdout, &mut seen, "two\n");
+    let elapsed = start.elapsed();
+    tailr.child.kill()?;
+    tailr.child.wait()?;
+
+    assert!(ok, "unexpected output: {:?}", seen);
+    assert!(elapsed < Duration::from_millis(800), "took {:?}", elapsed);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_default() -> TestResult {
+    run_stdin(&["-"], TEN, "tests/expected/ten.txt.out")
+}
+
+#[test]
+fn stdin_n3() -> TestResult {
+    run_stdin(&["-n", "3", "-"], TEN, "tests/expected/ten.txt.n3.out")
+}
+
+#[test]
+fn stdin_n200() -> TestResult {
+    run_stdin(&["-n", "200", "-"], TEN, "tests/expected/ten.txt.n200.out")
+}
+
+#[test]
+fn stdin_n_plus_2() -> TestResult {
+    run_stdin(&["-n", "+2", "-"], TEN, "tests/expected/ten.txt.n+2.out")
+}
+
+#[test]
+fn stdin_n0() -> TestResult {
+    run_stdin(&["-n", "0", "-"], TEN, "tests/expected/ten.txt.n0.out")
+}
+
+#[test]
+fn stdin_c10() -> TestResult {
+    run_stdin(&["-c", "10", "-"], TEN, "tests/expected/ten.txt.c10.out")
+}
+
+#[test]
+fn stdin_c_plus_2() -> TestResult {
+    run_stdin(&["-c", "+2", "-"], TEN, "tests/expected/ten.txt.c+2.out")
+}
+
+#[test]
+fn stdin_crlf_n3() -> TestResult {
+    run_stdin(&["-n", "3", "-"], THREE, "tests/expected/three.txt.n3.out")
+}
+
+#[test]
+fn stdin_c10_matches_file() -> TestResult {
+    run(&["-c", "10", TEN], "tests/expected/ten.txt.c10.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_with_files_prints_header() -> TestResult {
+    let input = fs::read_to_string(THREE)?;
+    let expected = format!(
+        "==> {} <==\nten\n\n==> standard input <==\nfour words.\n",
+        TEN
+    );
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", TEN, "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn long_c1k() -> TestResult {
+    run(&["-c", "1k", LONG], "tests/expected/long.txt.c1k.out")
+}
+
+#[test]
+fn long_c_minus_1k() -> TestResult {
+    run(&["-c=-1K", LONG], "tests/expected/long.txt.c1k.out")
+}
+
+#[test]
+fn long_c_2b() -> TestResult {
+    run(&["-c", "2b", LONG], "tests/expected/long.txt.c1k.out")
+}
+
+#[test]
+fn long_c_plus_1k() -> TestResult {
+    run(&["-c", "+1k", LONG], "tests/expected/long.txt.c+1k.out")
+}
+
+#[test]
+fn long_n1k() -> TestResult {
+    run(&["-n", "1k", LONG], "tests/expected/long.txt.n1k.out")
+}
+
+#[test]
+fn dies_bad_suffix() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "10x", LONG])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal byte count -- 10x"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn verbose_one_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-v", "-n", "2", TEN])
+        .assert()
+        .success()
+        .stdout(format!("==> {} <==\nnine\nten\n", TEN));
+
+    Ok(())
+}
+
+#[test]
+fn quiet_three_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-q", "-n", "1", TEN, EMPTY, TEN])
+        .assert()
+        .success()
+        .stdout("ten\nten\n");
+
+    Ok(())
+}
+
+#[test]
+fn headers_three_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", TEN, EMPTY, TEN])
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {} <==\nten\n\n==> {} <==\n\n==> {} <==\nten\n",
+            TEN, EMPTY, TEN
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn headers_skip_bad_first_file() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", &bad, TEN])
+        .assert()
+        .stdout(format!("==> {} <==\nten\n", TEN));
+
+    Ok(())
+}
+
+#[test]
+fn dies_quiet_and_verbose() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-q", "-v", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_c_plus_1() -> TestResult {
+    run(&["-c", "+1", BINARY], "tests/expected/binary.bin.c+1.out")
+}
+
+#[test]
+fn binary_c100() -> TestResult {
+    run(&["-c", "100", BINARY], "tests/expected/binary.bin.c100.out")
+}
+
+#[test]
+fn binary_n3() -> TestResult {
+    run(&["-n", "3", BINARY], "tests/expected/binary.bin.n3.out")
+}
+
+#[test]
+fn binary_n_plus_5() -> TestResult {
+    run(&["-n", "+5", BINARY], "tests/expected/binary.bin.n+5.out")
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn copy_memory_is_bounded() -> TestResult {
+    // A 34MB file under a 16MB address space limit, which is only
+    // enough if the output is copied in chunks rather than read whole
+    let dir = tempdir()?;
+    let path = dir.path().join("big.txt");
+    fs::write(&path, "0123456789abcdef\n".repeat(2_000_000))?;
+
+    let prg = assert_cmd::cargo::cargo_bin(PRG);
+    for (args, len) in [
+        (["-c", "+1"], 34_000_000),
+        (["-n", "+1"], 34_000_000),
+        (["-c", "30M"], 30 * 1024 * 1024),
+    ] {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("ulimit -v 16000 && exec \"$0\" \"$@\"")
+            .arg(&prg)
+            .args(args)
+            .arg(&path)
+            .output()?;
+        assert!(output.status.success(), "{:?}", args);
+        assert_eq!(output.stdout.len(), len, "{:?}", args);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bad_file_exit_status() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read("tests/expected/ten.txt.n1.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-q", "-n", "1", &bad, TEN])
+        .assert()
+        .code(1)
+        .stdout(expected)
+        .stderr(predicate::str::starts_with(format!("tailr: {}: ", bad)));
+
+    Ok(())
+}
+
+#[test]
+fn bad_file_last_exit_status() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", TEN, &bad])
+        .assert()
+        .code(1)
+        .stdout(format!("==> {} <==\nten\n", TEN))
+        .stderr(predicate::str::contains(format!("tailr: {}: ", bad)));
+
+    Ok(())
+}
+
+#[test]
+fn good_files_exit_status() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", TEN, ONE])
+        .assert()
+        .success()
+        .stderr("");
+
+    Ok(())
+}
+
+#[test]
+fn follow_bad_file_exit_status() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad])
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(format!("tailr: {}: ", bad)));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_n_plus_0() -> TestResult {
+    run_stdin(&["-n", "+0", "-"], TEN, "tests/expected/ten.txt.n+0.out")
+}
+
+#[test]
+fn stdin_c_plus_0() -> TestResult {
+    run_stdin(&["-c", "+0", "-"], TEN, "tests/expected/ten.txt.c+0.out")
+}
+
+#[test]
+fn plus_0_same_as_plus_1() -> TestResult {
+    for flag in ["-n", "-c"] {
+        let plus_0 = Command::cargo_bin(PRG)?.args([flag, "+0", TEN]).output()?;
+        let plus_1 = Command::cargo_bin(PRG)?.args([flag, "+1", TEN]).output()?;
+        assert_eq!(plus_0.stdout, fs::read(TEN)?);
+        assert_eq!(plus_0.stdout, plus_1.stdout);
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn plus_lines_file_matches_stdin() -> TestResult {
+    // Counting and printing read the same handle, so the file gives
+    // the same output as streaming it through stdin
+    for input in [TEN, THREE, LONG, BINARY] {
+        for count in ["+1", "+2", "+5", "+100"] {
+            let from_file = Command::cargo_bin(PRG)?
+                .args(["-n", count, input])
+                .output()?;
+            let from_stdin = Command::cargo_bin(PRG)?
+                .args(["-n", count, "-"])
+                .write_stdin(fs::read(input)?)
+                .output()?;
+            assert!(from_file.status.success());
+            assert_eq!(from_file.stdout, from_stdin.stdout, "{} {}", input, count);
+        }
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+fn run_fifo(args: &[&str], expected: &str) -> TestResult {
+    let dir = tempdir()?;
+    let fifo = dir.path().join("fifo");
+    assert!(std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()?
+        .success());
+
+    // Opening the FIFO for writing blocks until tailr opens it to read
+    let writer = {
+        let fifo = fifo.clone();
+        thread::spawn(move || fs::write(fifo, fs::read(TEN)?))
+    };
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .arg(&fifo)
+        .timeout(Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    writer.join().unwrap()?;
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn fifo_n3() -> TestResult {
+    run_fifo(&["-n", "3"], "eight\nnine\nten\n")
+}
+
+#[test]
+#[cfg(unix)]
+fn fifo_n_plus_9() -> TestResult {
+    run_fifo(&["-n", "+9"], "nine\nten\n")
+}
+
+#[test]
+#[cfg(unix)]
+fn fifo_c4() -> TestResult {
+    run_fifo(&["-c", "4"], "ten\n")
+}
+
+#[test]
+#[cfg(unix)]
+fn dev_stdin_n3() -> TestResult {
+    run_stdin(
+        &["-n", "3", "/dev/stdin"],
+        TEN,
+        "tests/expected/ten.txt.n3.out",
+    )
+}
+
+#[test]
+fn directory_error() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", TEN])
+        .assert()
+        .code(1)
+        .stdout(format!(
+            "==> tests/inputs <==\n\n==> {} <==\n{}",
+            TEN,
+            fs::read_to_string(TEN)?
+        ))
+        .stderr(predicate::str::starts_with("tailr: tests/inputs: "));
+
+    Ok(())
+}